@@ -0,0 +1,293 @@
+// GENERATED CODE - DO NOT MODIFY BY HAND.
+// Regenerate with `cargo run -- bindings`.
+
+part of 'gpu.dart';
+
+abstract base class _ArgumentTableNatives extends NativeFieldWrapperClass1 {
+  @pragma('vm:external-name', 'ArgumentTable_set_buffer')
+  external void setBuffer(Buffer buffer, int index, [int? offset]);
+
+  @pragma('vm:external-name', 'ArgumentTable_set_texture')
+  external void setTexture(Texture texture, int index);
+}
+
+abstract base class _BufferNatives extends NativeFieldWrapperClass1 {
+  /// The buffer's storage as a `Uint8List`, without copying. Writes land in the buffer
+  /// directly, and the list keeps the buffer alive.
+  @pragma('vm:external-name', 'Buffer_contents')
+  external Uint8List contents();
+
+  /// The buffer's storage as a `Float32List`, for writing vertex data in place.
+  @pragma('vm:external-name', 'Buffer_float32_contents')
+  external Float32List float32Contents();
+
+  @pragma('vm:external-name', 'Buffer_gpu_address')
+  external int gpuAddress();
+
+  @pragma('vm:external-name', 'Buffer_label')
+  external String? label();
+
+  @pragma('vm:external-name', 'Buffer_length')
+  external int length();
+
+  @pragma('vm:external-name', 'Buffer_set_contents')
+  external void setContents(TypedData data);
+
+  @pragma('vm:external-name', 'Buffer_set_label')
+  external void setLabel(String label);
+}
+
+abstract base class _CommandBufferNatives extends NativeFieldWrapperClass1 {
+  @pragma('vm:external-name', 'CommandBuffer_compute_command_encoder')
+  external ComputeCommandEncoder computeCommandEncoder();
+
+  @pragma('vm:external-name', 'CommandBuffer_drawable')
+  external Texture drawable();
+
+  @pragma('vm:external-name', 'CommandBuffer_render_command_encoder')
+  external RenderCommandEncoder _renderCommandEncoder(
+    RenderPassDescriptor descriptor,
+  );
+}
+
+abstract base class _ComputeCommandEncoderNatives
+    extends NativeFieldWrapperClass1 {
+  @pragma(
+    'vm:external-name',
+    'ComputeCommandEncoder_build_acceleration_structure',
+  )
+  external void _buildAccelerationStructure(
+    AccelerationStructure accelerationStructure,
+    AccelerationStructureDescriptor descriptor,
+    BufferRange scratchBufferRange,
+  );
+
+  @pragma('vm:external-name', 'ComputeCommandEncoder_consumer_barrier')
+  external void _consumerBarrier(
+    int afterEncoderStages,
+    int beforeEncoderStages,
+    int visibilityOptions,
+  );
+
+  @pragma('vm:external-name', 'ComputeCommandEncoder_copy')
+  external void copy(Texture sourceTexture, Texture destinationTexture);
+
+  @pragma('vm:external-name', 'ComputeCommandEncoder_dispatch_threadgroups')
+  external void dispatchThreadgroups(
+    int threadgroupsPerGridX,
+    int threadgroupsPerGridY,
+    int threadgroupsPerGridZ,
+    int threadsPerThreadgroupX,
+    int threadsPerThreadgroupY,
+    int threadsPerThreadgroupZ,
+  );
+
+  /// threadsPerGrid - [texture.width, texture.height, texture.depth]
+  ///
+  /// threadsPerThreadgroupY - (numthreadgroups) [8, 8, 1]
+  ///
+  /// This should handle partial edges automatically
+  @pragma('vm:external-name', 'ComputeCommandEncoder_dispatch_threads')
+  external void dispatchThreads(
+    int threadsPerGridX,
+    int threadsPerGridY,
+    int threadsPerGridZ,
+    int threadsPerThreadgroupX,
+    int threadsPerThreadgroupY,
+    int threadsPerThreadgroupZ,
+  );
+
+  @pragma('vm:external-name', 'ComputeCommandEncoder_end_encoding')
+  external void endEncoding();
+
+  @pragma('vm:external-name', 'ComputeCommandEncoder_generate_mipmaps')
+  external void generateMipmaps(Texture texture);
+
+  @pragma('vm:external-name', 'ComputeCommandEncoder_intra_pass_barrier')
+  external void _intraPassBarrier(
+    int afterEncoderStages,
+    int beforeEncoderStages,
+    int visibilityOptions,
+  );
+
+  @pragma('vm:external-name', 'ComputeCommandEncoder_producer_barrier')
+  external void _producerBarrier(
+    int afterEncoderStages,
+    int beforeEncoderStages,
+    int visibilityOptions,
+  );
+
+  @pragma('vm:external-name', 'ComputeCommandEncoder_set_argument_table')
+  external void setArgumentTable(ArgumentTable argumentTable);
+
+  @pragma('vm:external-name', 'ComputeCommandEncoder_set_compute_pipeline')
+  external void setComputePipeline(ComputePipeline computePipeline);
+}
+
+abstract base class _ComputePipelineNatives extends NativeFieldWrapperClass1 {
+  /// Takes over the pipeline the previous run retained under `name`, if there is one.
+  @pragma('vm:external-name', 'ComputePipeline_attach')
+  external bool _attach(String name);
+
+  /// Keeps the compiled pipeline across hot restarts under `name`.
+  @pragma('vm:external-name', 'ComputePipeline_retain')
+  external void _retain(String name);
+}
+
+abstract base class _GpuNatives extends NativeFieldWrapperClass1 {
+  @pragma('vm:external-name', 'Gpu_acceleration_structure_sizes')
+  external Map<String, dynamic> _accelerationStructureSizes(
+    AccelerationStructureDescriptor descriptor,
+  );
+
+  @pragma('vm:external-name', 'Gpu_add_acceleration_structure_to_residency_set')
+  external void addAccelerationStructureToResidencySet(
+    AccelerationStructure accelerationStructure,
+  );
+
+  @pragma('vm:external-name', 'Gpu_add_buffer_to_residency_set')
+  external void addBufferToResidencySet(Buffer buffer);
+
+  @pragma('vm:external-name', 'Gpu_add_texture_to_residency_set')
+  external void addTextureToResidencySet(Texture texture);
+
+  /// Takes over the GPU the previous run retained under `name`, presenting to `window`.
+  @pragma('vm:external-name', 'Gpu_attach')
+  external bool _attach(String name, Window window);
+
+  @pragma('vm:external-name', 'Gpu_begin_command_buffer')
+  external CommandBuffer beginCommandBuffer();
+
+  @pragma('vm:external-name', 'Gpu_commit_residency_set')
+  external void commitResidencySet();
+
+  @pragma('vm:external-name', 'Gpu_compile_compute_pipeline')
+  external ComputePipeline compileComputePipeline(
+    ComputePipelineDescriptor descriptor,
+  );
+
+  @pragma('vm:external-name', 'Gpu_compile_render_pipeline')
+  external RenderPipeline compileRenderPipeline(
+    RenderPipelineDescriptor descriptor,
+  );
+
+  /// Compiles the shaders on a worker thread so the frame loop keeps running, then
+  /// creates the pipeline state back on the isolate thread.
+  @pragma('vm:external-name', 'Gpu_compile_render_pipeline_async')
+  external void _compileRenderPipelineAsync(
+    SendPort port,
+    RenderPipelineDescriptor descriptor,
+  );
+
+  @pragma('vm:external-name', 'Gpu_create_acceleration_structure')
+  external AccelerationStructure createAccelerationStructure(int size);
+
+  @pragma('vm:external-name', 'Gpu_create_argument_table')
+  external ArgumentTable _createArgumentTable(
+    int maxBufferBindCount,
+    int maxTextureBindCount,
+    int maxSamplerStateBindCount,
+  );
+
+  @pragma('vm:external-name', 'Gpu_create_buffer')
+  external Buffer createBuffer(int length);
+
+  @pragma('vm:external-name', 'Gpu_create_texture')
+  external Texture createTexture(int width, int height, int pixelFormat);
+
+  @pragma('vm:external-name', 'Gpu_end_command_buffer')
+  external void endCommandBuffer(CommandBuffer commandBuffer);
+
+  @pragma('vm:external-name', 'Gpu_init')
+  external void _initGpu(Window window);
+
+  /// Keeps the device, queues and residency set across hot restarts under `name`.
+  @pragma('vm:external-name', 'Gpu_retain')
+  external void _retain(String name);
+}
+
+abstract base class _RenderCommandEncoderNatives
+    extends NativeFieldWrapperClass1 {
+  @pragma('vm:external-name', 'RenderCommandEncoder_consumer_barrier')
+  external void _consumerBarrier(
+    int afterEncoderStages,
+    int beforeEncoderStages,
+    int visibilityOptions,
+  );
+
+  @pragma('vm:external-name', 'RenderCommandEncoder_draw_primitives')
+  external void _drawPrimitives(
+    int primitiveType,
+    int vertexCount,
+    int instanceCount,
+    int vertexStart,
+    int baseInstance,
+  );
+
+  @pragma('vm:external-name', 'RenderCommandEncoder_end_encoding')
+  external void endEncoding();
+
+  @pragma('vm:external-name', 'RenderCommandEncoder_intra_pass_barrier')
+  external void _intraPassBarrier(
+    int afterEncoderStages,
+    int beforeEncoderStages,
+    int visibilityOptions,
+  );
+
+  @pragma('vm:external-name', 'RenderCommandEncoder_producer_barrier')
+  external void _producerBarrier(
+    int afterEncoderStages,
+    int beforeEncoderStages,
+    int visibilityOptions,
+  );
+
+  @pragma('vm:external-name', 'RenderCommandEncoder_set_argument_table')
+  external void setArgumentTable(ArgumentTable argumentTable);
+
+  @pragma('vm:external-name', 'RenderCommandEncoder_set_cull_mode')
+  external void _setCullMode(int mode);
+
+  @pragma('vm:external-name', 'RenderCommandEncoder_set_render_pipeline')
+  external void setRenderPipeline(RenderPipeline renderPipeline);
+
+  @pragma('vm:external-name', 'RenderCommandEncoder_set_scissor_rect')
+  external void _setScissorRect(int x, int y, int width, int height);
+
+  @pragma('vm:external-name', 'RenderCommandEncoder_set_viewport')
+  external void _setViewport(double x, double y, double width, double height);
+}
+
+abstract base class _RenderPipelineNatives extends NativeFieldWrapperClass1 {
+  /// Takes over the pipeline the previous run retained under `name`, if there is one.
+  @pragma('vm:external-name', 'RenderPipeline_attach')
+  external bool _attach(String name);
+
+  /// Keeps the compiled pipeline across hot restarts under `name`.
+  @pragma('vm:external-name', 'RenderPipeline_retain')
+  external void _retain(String name);
+}
+
+abstract base class _TextureNatives extends NativeFieldWrapperClass1 {
+  @pragma('vm:external-name', 'Texture_height')
+  external int height();
+
+  @pragma('vm:external-name', 'Texture_pixel_format')
+  external int pixelFormat();
+
+  @pragma('vm:external-name', 'Texture_replace_region')
+  external void _replaceRegion(
+    int regionX,
+    int regionY,
+    int regionZ,
+    int regionWidth,
+    int regionHeight,
+    int regionDepth,
+    int mipmapLevel,
+    TypedData data,
+    int bytesPerRow,
+    int bytesPerImage,
+  );
+
+  @pragma('vm:external-name', 'Texture_width')
+  external int width();
+}
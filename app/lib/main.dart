@@ -66,15 +66,19 @@ class SimpleRaster {
 }
 
 void main() {
-  final window = Window(width: 800, height: 600, title: 'Hello World');
-  final gpu = Gpu(window);
+  // Retained, so a hot restart keeps the window open and the GPU initialized.
+  final window = Window.retained(
+    'main',
+    width: 800,
+    height: 600,
+    title: 'Hello World',
+  );
+  final gpu = Gpu.retained('main', window);
 
   final world = World();
   world.insertResource(SimpleRaster(gpu));
   window.onUpdate(() => update(world));
   window.onPresent((interpolation) => present(world, gpu, interpolation));
-
-  while (window.poll()) {}
 }
 
 // update game logic at 60 ticks
@@ -0,0 +1,20 @@
+// GENERATED CODE - DO NOT MODIFY BY HAND.
+// Regenerate with `cargo run -- bindings`.
+
+part of 'native.dart';
+
+/// Spawns a worker from Dart. The worker runs `entryPoint` of `script` with `port`, and
+/// may only resolve the natives of the default namespace and of `namespaces`, so it
+/// can't touch windows or the GPU unless asked to.
+@pragma('vm:external-name', 'spawn_worker')
+external void _spawnWorker(
+  String script,
+  String packageConfig,
+  String entryPoint,
+  SendPort port, [
+  List<String>? namespaces,
+]);
+
+/// Runs the completion of the async work posted as `token`, returning its Dart result.
+@pragma('vm:external-name', 'take_async_result')
+external Object? _takeAsyncResult(int token);
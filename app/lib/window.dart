@@ -0,0 +1,27 @@
+import 'dart:nativewrappers';
+
+part 'window.g.dart';
+
+base class Window extends _WindowNatives {
+  Window({required int width, required int height, required String title}) {
+    createWindow(width, height, title);
+  }
+
+  Window._();
+
+  /// A window that stays open across hot restarts: the one the previous run kept under
+  /// [name], or a new one.
+  factory Window.retained(
+    String name, {
+    required int width,
+    required int height,
+    required String title,
+  }) {
+    final window = Window._();
+    if (!window._attach(name)) {
+      window.createWindow(width, height, title);
+    }
+    window._retain(name);
+    return window;
+  }
+}
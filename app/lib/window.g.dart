@@ -0,0 +1,28 @@
+// GENERATED CODE - DO NOT MODIFY BY HAND.
+// Regenerate with `cargo run -- bindings`.
+
+part of 'window.dart';
+
+abstract base class _WindowNatives extends NativeFieldWrapperClass1 {
+  /// Takes over the window the previous run retained under `name`, if there is one.
+  @pragma('vm:external-name', 'attach_window')
+  external bool _attach(String name);
+
+  @pragma('vm:external-name', 'create_window')
+  external void createWindow(int width, int height, String title);
+
+  @pragma('vm:external-name', 'on_present')
+  external void onPresent(void Function(double interpolation) callback);
+
+  @pragma('vm:external-name', 'on_update')
+  external void onUpdate(void Function() callback);
+
+  /// Steps the window once. The engine already does this every frame; only needed by code
+  /// that drives its own loop.
+  @pragma('vm:external-name', 'poll')
+  external bool poll();
+
+  /// Keeps the window open across hot restarts under `name`.
+  @pragma('vm:external-name', 'retain_window')
+  external void _retain(String name);
+}
@@ -0,0 +1,75 @@
+//! Resolution cost with thousands of registered natives, comparing the indexed lookup
+//! `native_resolver` uses against a linear scan of the registry.
+//!
+//! Run with `cargo bench --bench native_resolution`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bigfish::dart_api::{self, NativeFunction};
+
+/// Keeps the bench natives apart from the ones the library registers.
+const NAMESPACE: &str = "bench";
+
+unsafe extern "C" fn noop(_args: dart_api::sys::Dart_NativeArguments) {}
+
+// 16 * 16 * 16 = 4096 natives named like `Alpha_alpha_Alpha`.
+macro_rules! register {
+    ([$($a:ident)*] $b:tt $c:tt) => {
+        $(register!(@b $a $b $c);)*
+    };
+    (@b $a:ident [$($b:ident)*] $c:tt) => {
+        $(register!(@c $a $b $c);)*
+    };
+    (@c $a:ident $b:ident [$($c:ident)*]) => {
+        $(::inventory::submit! {
+            NativeFunction::new(
+                concat!(stringify!($a), "_", stringify!($b), "_", stringify!($c)),
+                noop,
+            )
+            .in_namespace(NAMESPACE)
+            .with_arity(1)
+        })*
+    };
+}
+
+register!(
+    [Alpha Bravo Charlie Delta Echo Foxtrot Golf Hotel India Juliett Kilo Lima Mike November Oscar Papa]
+    [alpha bravo charlie delta echo foxtrot golf hotel india juliett kilo lima mike november oscar papa]
+    [Alpha Bravo Charlie Delta Echo Foxtrot Golf Hotel India Juliett Kilo Lima Mike November Oscar Papa]
+);
+
+const ROUNDS: usize = 20;
+
+fn main() {
+    let names: Vec<&'static str> = inventory::iter::<NativeFunction>()
+        .filter(|function| function.namespace() == NAMESPACE)
+        .map(|function| function.name())
+        .collect();
+    println!("{} registered natives", names.len());
+
+    let start = Instant::now();
+    dart_api::resolve(NAMESPACE, names[0], 1).unwrap();
+    println!("index build + first lookup: {:?}", start.elapsed());
+
+    let linear = measure(&names, |name| {
+        inventory::iter::<NativeFunction>()
+            .find(|function| function.name() == name)
+            .is_some()
+    });
+    let indexed = measure(&names, |name| dart_api::resolve(NAMESPACE, name, 1).is_ok());
+
+    println!("linear scan: {:?}/lookup", linear);
+    println!("indexed:     {:?}/lookup", indexed);
+}
+
+/// Average time to resolve one name, over `ROUNDS` passes through every registered name.
+fn measure(names: &[&'static str], lookup: impl Fn(&str) -> bool) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for name in names {
+            assert!(black_box(lookup(black_box(name))));
+        }
+    }
+    start.elapsed() / (ROUNDS * names.len()) as u32
+}
@@ -1,52 +1,50 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, ImplItem, ItemFn, ItemImpl, Type};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Attribute, DeriveInput, FnArg, GenericArgument, ImplItem, ItemFn, ItemImpl,
+    LitStr, Pat, PathArguments, ReturnType, Signature, Type,
+};
 
+/// Registers a free function as a Dart native under its own name.
+///
+/// `#[native_func(class = "Window")]` declares it as an instance method of `Window` in the
+/// generated bindings, with the first argument as the receiver; without `class` it is a
+/// top-level function. `namespace = "gpu"` registers it for the libraries mapped to that
+/// namespace instead of the default `app`.
 #[proc_macro_attribute]
-pub fn native_func(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn native_func(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut class = None;
+    let mut namespace = None;
+    let attr_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("class") {
+            class = Some(meta.value()?.parse::<LitStr>()?.value());
+            Ok(())
+        } else if meta.path.is_ident("namespace") {
+            namespace = Some(meta.value()?.parse::<LitStr>()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported native_func argument"))
+        }
+    });
+    parse_macro_input!(attr with attr_parser);
+
     // Parse the input function
-    let input_fn = parse_macro_input!(item as ItemFn);
+    let mut input_fn = parse_macro_input!(item as ItemFn);
+    let dart = match DartAttrs::take(&mut input_fn.attrs, &mut input_fn.sig) {
+        Ok(dart) => dart,
+        Err(error) => return error.to_compile_error().into(),
+    };
     let fn_name = &input_fn.sig.ident;
     let shim_name = syn::Ident::new(&format!("__shim_{}", fn_name), fn_name.span());
 
-    // Check if the function has a scope parameter
-    let has_scope = input_fn.sig.inputs.iter().any(|arg| {
-        if let syn::FnArg::Typed(pat_type) = arg {
-            // Check for direct Scope type
-            if let syn::Type::Path(type_path) = &*pat_type.ty {
-                if let Some(last_segment) = type_path.path.segments.last() {
-                    if last_segment.ident == "Scope" {
-                        return true;
-                    }
-                }
-            }
-            // Check for reference to Scope (&Scope)
-            if let syn::Type::Reference(type_ref) = &*pat_type.ty {
-                if let syn::Type::Path(type_path) = &*type_ref.elem {
-                    if let Some(last_segment) = type_path.path.segments.last() {
-                        if last_segment.ident == "Scope" {
-                            return true;
-                        }
-                    }
-                }
-            }
-        }
-        false
-    });
-
-    // Generate the shim call based on whether scope is present
-    let shim_call = if has_scope {
-        quote! {
-            let args = crate::dart_api::NativeArguments::from_raw(args);
-            let scope = ::std::mem::ManuallyDrop::into_inner(crate::dart_api::Isolate::current().unwrap());
-            #fn_name(args, scope);
-        }
-    } else {
-        quote! {
-            let args = crate::dart_api::NativeArguments::from_raw(args);
-            #fn_name(args);
-        }
+    let shim_body = shim_body(quote!(#fn_name), &input_fn.sig, &fn_name.to_string());
+    let signature = match dart.signature(class.as_deref(), &input_fn.sig) {
+        Ok(signature) => signature,
+        Err(error) => return error.to_compile_error().into(),
     };
+    let arity = dart.arity(class.as_deref(), &input_fn.sig);
+    let namespace = namespace.map(|namespace| quote!(.in_namespace(#namespace)));
 
     // Generate the original function + the shim + the inventory submission
     let expanded = quote! {
@@ -54,23 +52,41 @@ pub fn native_func(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
         #[no_mangle]
         pub unsafe extern "C" fn #shim_name(args: crate::dart_api::sys::Dart_NativeArguments) {
-            #shim_call
+            #shim_body
         }
 
         ::inventory::submit! {
             crate::dart_api::NativeFunction::new(::core::stringify!(#fn_name), #shim_name)
+                #namespace
+                #arity
+                #signature
         }
     };
 
     TokenStream::from(expanded)
 }
 
+/// Registers the associated functions of an impl block as Dart natives named
+/// `<Type>_<function>`, declared on the Dart class `<Type>`. Takes the same `namespace`
+/// argument as [`native_func`](macro@native_func).
 #[proc_macro_attribute]
-pub fn native_impl(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    let input_impl = parse_macro_input!(item as ItemImpl);
-    let self_ty = &*input_impl.self_ty;
+pub fn native_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut namespace = None;
+    let attr_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("namespace") {
+            namespace = Some(meta.value()?.parse::<LitStr>()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported native_impl argument"))
+        }
+    });
+    parse_macro_input!(attr with attr_parser);
+    let namespace = namespace.map(|namespace| quote!(.in_namespace(#namespace)));
 
-    let type_tag = match self_ty {
+    let mut input_impl = parse_macro_input!(item as ItemImpl);
+    let self_ty = input_impl.self_ty.clone();
+
+    let type_tag = match &*self_ty {
         Type::Path(tp) if tp.qself.is_none() => tp
             .path
             .segments
@@ -82,7 +98,7 @@ pub fn native_impl(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
     let mut shim_items = Vec::new();
 
-    for impl_item in &input_impl.items {
+    for impl_item in &mut input_impl.items {
         let ImplItem::Fn(f) = impl_item else {
             continue;
         };
@@ -98,6 +114,14 @@ pub fn native_impl(_attr: TokenStream, item: TokenStream) -> TokenStream {
             continue;
         }
 
+        let dart = match DartAttrs::take(&mut f.attrs, &mut f.sig) {
+            Ok(dart) => dart,
+            Err(error) => {
+                shim_items.push(error.to_compile_error());
+                continue;
+            }
+        };
+
         let fn_name = &f.sig.ident;
         let shim_name =
             syn::Ident::new(&format!("__shim_{}_{}", type_tag, fn_name), fn_name.span());
@@ -105,56 +129,35 @@ pub fn native_impl(_attr: TokenStream, item: TokenStream) -> TokenStream {
             syn::Ident::new(&format!("{}_{}", type_tag, f.sig.ident), f.sig.ident.span());
 
         let fn_attrs = &f.attrs;
-
-        // Check if the function has a scope parameter
-        let has_scope = f.sig.inputs.iter().any(|arg| {
-            if let syn::FnArg::Typed(pat_type) = arg {
-                // Check for direct Scope type
-                if let syn::Type::Path(type_path) = &*pat_type.ty {
-                    if let Some(last_segment) = type_path.path.segments.last() {
-                        if last_segment.ident == "Scope" {
-                            return true;
-                        }
-                    }
-                }
-                // Check for reference to Scope (&Scope)
-                if let syn::Type::Reference(type_ref) = &*pat_type.ty {
-                    if let syn::Type::Path(type_path) = &*type_ref.elem {
-                        if let Some(last_segment) = type_path.path.segments.last() {
-                            if last_segment.ident == "Scope" {
-                                return true;
-                            }
-                        }
-                    }
-                }
-            }
-            false
-        });
-
-        // Generate the shim call based on whether scope is present
-        let shim_call = if has_scope {
-            quote! {
-                let args = crate::dart_api::NativeArguments::from_raw(args);
-                let scope = crate::dart_api::Isolate::current().unwrap();
-                #self_ty::#fn_name(args, scope);
-            }
-        } else {
-            quote! {
-                let args = crate::dart_api::NativeArguments::from_raw(args);
-                #self_ty::#fn_name(args);
+        // Only `#[cfg]` applies to the registration; lint attributes would be unused there.
+        let cfg_attrs = f.attrs.iter().filter(|attr| attr.path().is_ident("cfg"));
+        let shim_body = shim_body(
+            quote!(<#self_ty>::#fn_name),
+            &f.sig,
+            &dart_fn_name.to_string(),
+        );
+        let signature = match dart.signature(Some(&type_tag), &f.sig) {
+            Ok(signature) => signature,
+            Err(error) => {
+                shim_items.push(error.to_compile_error());
+                continue;
             }
         };
+        let arity = dart.arity(Some(&type_tag), &f.sig);
 
         shim_items.push(quote! {
             #(#fn_attrs)*
             #[no_mangle]
             pub unsafe extern "C" fn #shim_name(args: crate::dart_api::sys::Dart_NativeArguments) {
-                #shim_call
+                #shim_body
             }
 
-            #(#fn_attrs)*
+            #(#cfg_attrs)*
             ::inventory::submit! {
                 crate::dart_api::NativeFunction::new(::core::stringify!(#dart_fn_name), #shim_name)
+                    #namespace
+                    #arity
+                    #signature
             }
         });
     }
@@ -166,3 +169,581 @@ pub fn native_impl(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
     TokenStream::from(expanded)
 }
+
+/// Implements `NativePeer` for a type, binding it to the Dart class of the same name.
+///
+/// `#[native_peer(external_size = "method")]` reports `self.method()` bytes of native
+/// memory to the Dart GC instead of the size of the type. `#[native_peer(library = ...)]`
+/// names the Dart library declaring the class, as a string or a constant, when it isn't
+/// `package:app/native.dart`.
+#[proc_macro_derive(NativePeer, attributes(native_peer))]
+pub fn derive_native_peer(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let ident = &input.ident;
+    let class_name = ident.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut external_size = None;
+    let mut library = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("native_peer"))
+    {
+        let parsed = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("external_size") {
+                let method: LitStr = meta.value()?.parse()?;
+                external_size = Some(method.parse::<syn::Ident>()?);
+                Ok(())
+            } else if meta.path.is_ident("library") {
+                library = Some(meta.value()?.parse::<syn::Expr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `external_size` or `library`"))
+            }
+        });
+        if let Err(error) = parsed {
+            return error.to_compile_error().into();
+        }
+    }
+    let external_size = external_size.map(|method| {
+        quote! {
+            fn external_size(&self) -> usize {
+                self.#method()
+            }
+        }
+    });
+
+    let library = library.map(|library| quote!(const LIBRARY: &'static str = #library;));
+
+    let expanded = quote! {
+        impl #impl_generics crate::dart_api::NativePeer for #ident #ty_generics #where_clause {
+            const CLASS_NAME: &'static str = #class_name;
+            #library
+            #external_size
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// How a typed native parameter is pulled out of `Dart_NativeArguments`.
+enum ArgKind<'a> {
+    /// `Scope<'_>` or `&Scope<'_>`: injected, does not consume a Dart argument.
+    Scope { by_ref: bool },
+    /// `&T` / `&mut T`: the peer stored on a `NativeFieldWrapperClass1` instance,
+    /// borrowed shared or exclusively for the call.
+    Peer { inner: &'a Type, mutable: bool },
+    /// Types with a direct `FromDartArg` conversion (primitives, `String`, handles).
+    Value,
+    /// Anything else is deserialized through `from_dart`.
+    Serde,
+}
+
+const VALUE_TYPES: &[&str] = &[
+    "bool",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "isize",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "usize",
+    "f32",
+    "f64",
+    "String",
+    "Handle",
+    "TypedDataView",
+    "SendPort",
+    "DartString",
+    "DartInt",
+    "DartList",
+    "DartMap",
+    "DartClosure",
+    "DartInstance",
+];
+
+fn last_ident(ty: &Type) -> Option<&syn::Ident> {
+    match ty {
+        Type::Path(type_path) => type_path.path.segments.last().map(|s| &s.ident),
+        _ => None,
+    }
+}
+
+fn classify(ty: &Type) -> ArgKind<'_> {
+    match ty {
+        Type::Reference(type_ref) => match last_ident(&type_ref.elem) {
+            Some(ident) if ident == "Scope" => ArgKind::Scope { by_ref: true },
+            _ => ArgKind::Peer {
+                inner: &type_ref.elem,
+                mutable: type_ref.mutability.is_some(),
+            },
+        },
+        _ => match last_ident(ty) {
+            Some(ident) if ident == "Scope" => ArgKind::Scope { by_ref: false },
+            Some(ident) if VALUE_TYPES.iter().any(|name| ident == name) => ArgKind::Value,
+            _ => ArgKind::Serde,
+        },
+    }
+}
+
+fn takes_native_arguments(sig: &Signature) -> bool {
+    sig.inputs.iter().any(|arg| match arg {
+        FnArg::Typed(pat_type) => {
+            last_ident(&pat_type.ty).is_some_and(|ident| ident == "NativeArguments")
+        }
+        FnArg::Receiver(_) => false,
+    })
+}
+
+/// Generates the body of the `extern "C"` shim that calls `callee`.
+///
+/// Functions taking `NativeArguments` get the raw arguments (and optionally a `Scope`)
+/// as before, and may return `Result<(), E>`. Any other signature is treated as typed:
+/// parameters are extracted by position and the return value is converted with
+/// `IntoDart`. A `TypedDataView` is acquired after every other parameter has been read,
+/// since the VM allows no other API calls until it is released, so a native takes at
+/// most one. Either way the call runs through `call_native`, so extraction failures,
+/// `Err` returns and panics are thrown in Dart as coming from `function`.
+fn shim_body(callee: TokenStream2, sig: &Signature, function: &str) -> TokenStream2 {
+    if takes_native_arguments(sig) {
+        let has_scope = sig.inputs.iter().any(|arg| match arg {
+            FnArg::Typed(pat_type) => matches!(classify(&pat_type.ty), ArgKind::Scope { .. }),
+            FnArg::Receiver(_) => false,
+        });
+
+        let (scope, call) = if has_scope {
+            (quote!(scope), quote!(#callee(args, scope)))
+        } else {
+            (quote!(_scope), quote!(#callee(args)))
+        };
+        let body = match &sig.output {
+            ReturnType::Default => quote! {
+                #call;
+                Ok(())
+            },
+            ReturnType::Type(..) => quote! {
+                #call.map_err(::core::convert::Into::<crate::dart_api::DartError>::into)
+            },
+        };
+
+        return quote! {
+            crate::dart_api::call_native(args, #function, |args, #scope| {
+                #body
+            });
+        };
+    }
+
+    let mut bindings = Vec::new();
+    let mut view = None;
+    let mut call_args = Vec::new();
+    let mut index = 0i32;
+    let mut uses_scope = !matches!(sig.output, ReturnType::Default);
+
+    for (position, input) in sig.inputs.iter().enumerate() {
+        let FnArg::Typed(pat_type) = input else {
+            continue;
+        };
+        let local = format_ident!("__arg{}", position);
+        let name = match &*pat_type.pat {
+            Pat::Ident(pat_ident) => pat_ident.ident.to_string(),
+            _ => format!("arg{}", position),
+        };
+        let ty = &*pat_type.ty;
+
+        let extract = match classify(ty) {
+            ArgKind::Scope { by_ref: true } => {
+                uses_scope = true;
+                call_args.push(quote!(&scope));
+                continue;
+            }
+            ArgKind::Scope { by_ref: false } => {
+                bindings.push(quote! {
+                    let #local = crate::dart_api::Isolate::current()?;
+                });
+                call_args.push(quote!(#local));
+                continue;
+            }
+            ArgKind::Peer { inner, mutable } => {
+                if last_ident(inner).is_some_and(|ident| ident == "str") {
+                    let msg = format!(
+                        "native parameter `{}`: use `String` instead of `&str`",
+                        name
+                    );
+                    return quote! { ::core::compile_error!(#msg); };
+                }
+                // The guard lives until the call returns, so the borrow covers it.
+                let (pat, borrow, arg) = if mutable {
+                    (quote!(mut #local), quote!(peer_mut), quote!(&mut *#local))
+                } else {
+                    (quote!(#local), quote!(peer), quote!(&*#local))
+                };
+                bindings.push(quote! {
+                    let #pat = args
+                        .get_arg(#index)
+                        .and_then(|handle| handle.#borrow::<#inner>())
+                        .map_err(|error| crate::dart_api::DartError::argument(#index, #name, error))?;
+                });
+                call_args.push(arg);
+                index += 1;
+                continue;
+            }
+            ArgKind::Value => quote! {
+                <#ty as crate::dart_api::FromDartArg>::from_arg(&args, #index)
+            },
+            ArgKind::Serde => quote! {
+                crate::dart_api::from_dart_arg::<#ty>(&args, #index)
+            },
+        };
+
+        let binding = quote! {
+            let #local = #extract
+                .map_err(|error| crate::dart_api::DartError::argument(#index, #name, error))?;
+        };
+        if last_ident(ty).is_some_and(|ident| ident == "TypedDataView") {
+            if view.replace(binding).is_some() {
+                let msg = format!(
+                    "native parameter `{}`: only one `TypedDataView` can be acquired at a time",
+                    name
+                );
+                return quote! { ::core::compile_error!(#msg); };
+            }
+        } else {
+            bindings.push(binding);
+        }
+        call_args.push(quote!(#local));
+        index += 1;
+    }
+    bindings.extend(view);
+
+    let call = quote!(#callee(#(#call_args),*));
+    let ret = match &sig.output {
+        ReturnType::Default => quote! {
+            #call;
+        },
+        ReturnType::Type(..) => quote! {
+            let value = crate::dart_api::IntoDart::into_dart(#call, &scope)?;
+            args.set_return_value(value);
+        },
+    };
+
+    let scope = if uses_scope {
+        quote!(scope)
+    } else {
+        quote!(_scope)
+    };
+
+    quote! {
+        crate::dart_api::call_native(args, #function, |args, #scope| {
+            #(#bindings)*
+            #ret
+            Ok(())
+        });
+    }
+}
+
+/// `#[dart(...)]` overrides for the generated Dart declaration of a native, plus the
+/// function's doc comment, which is carried over to Dart.
+///
+/// On the function: `name = "_createArgumentTable"`, `returns = "Map<String, dynamic>"`, or
+/// a full `signature = "Texture drawable()"` (required for natives taking
+/// `NativeArguments`, whose parameters can't be derived). On a parameter:
+/// `type = "void Function()"`.
+#[derive(Default)]
+struct DartAttrs {
+    name: Option<String>,
+    returns: Option<String>,
+    signature: Option<String>,
+    param_types: Vec<Option<String>>,
+    docs: Vec<String>,
+}
+
+impl DartAttrs {
+    /// Removes the `#[dart]` attributes from the function and its parameters.
+    fn take(attrs: &mut Vec<Attribute>, sig: &mut Signature) -> syn::Result<Self> {
+        let mut dart = DartAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("doc")) {
+            if let syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(doc),
+                        ..
+                    }),
+                ..
+            }) = &attr.meta
+            {
+                let doc = doc.value();
+                dart.docs
+                    .push(doc.strip_prefix(' ').unwrap_or(&doc).to_string());
+            }
+        }
+        for attr in take_dart_attrs(attrs) {
+            attr.parse_nested_meta(|meta| {
+                let value = Some(meta.value()?.parse::<LitStr>()?.value());
+                if meta.path.is_ident("name") {
+                    dart.name = value;
+                } else if meta.path.is_ident("returns") {
+                    dart.returns = value;
+                } else if meta.path.is_ident("signature") {
+                    dart.signature = value;
+                } else {
+                    return Err(meta.error("expected `name`, `returns` or `signature`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        for input in &mut sig.inputs {
+            let mut param_type = None;
+            if let FnArg::Typed(pat_type) = input {
+                for attr in take_dart_attrs(&mut pat_type.attrs) {
+                    attr.parse_nested_meta(|meta| {
+                        if meta.path.is_ident("type") {
+                            param_type = Some(meta.value()?.parse::<LitStr>()?.value());
+                            Ok(())
+                        } else {
+                            Err(meta.error("expected `type`"))
+                        }
+                    })?;
+                }
+            }
+            dart.param_types.push(param_type);
+        }
+
+        Ok(dart)
+    }
+
+    /// Builds the `.with_arity(...)` call for the inventory submission: the typed
+    /// parameters that aren't a `Scope`, or the declared parameters plus the receiver for a
+    /// `NativeArguments` function with a `signature`. Without either nothing is emitted and
+    /// the native resolves at any arity.
+    fn arity(&self, class: Option<&str>, sig: &Signature) -> TokenStream2 {
+        let arity = if !takes_native_arguments(sig) {
+            sig.inputs
+                .iter()
+                .filter(|input| match input {
+                    FnArg::Typed(pat_type) => {
+                        !matches!(classify(&pat_type.ty), ArgKind::Scope { .. })
+                    }
+                    FnArg::Receiver(_) => false,
+                })
+                .count()
+        } else if let Some((_, _, params)) =
+            self.signature.as_deref().and_then(parse_dart_signature)
+        {
+            params.len() + usize::from(class.is_some())
+        } else {
+            return TokenStream2::new();
+        };
+        quote!(.with_arity(#arity))
+    }
+
+    /// Builds the `.with_signature(...)` call for the inventory submission, or nothing
+    /// when the Dart declaration can't be derived.
+    fn signature(&self, class: Option<&str>, sig: &Signature) -> syn::Result<TokenStream2> {
+        let (dart_name, return_type, params) = if let Some(signature) = &self.signature {
+            parse_dart_signature(signature).ok_or_else(|| {
+                syn::Error::new(
+                    sig.ident.span(),
+                    format!("could not parse Dart signature `{}`", signature),
+                )
+            })?
+        } else if takes_native_arguments(sig) {
+            return Ok(TokenStream2::new());
+        } else {
+            let mut params = Vec::new();
+            let mut receiver = class.is_some();
+            for (input, param_type) in sig.inputs.iter().zip(&self.param_types) {
+                let FnArg::Typed(pat_type) = input else {
+                    continue;
+                };
+                if matches!(classify(&pat_type.ty), ArgKind::Scope { .. }) {
+                    continue;
+                }
+                if std::mem::take(&mut receiver) {
+                    continue;
+                }
+                let name = match &*pat_type.pat {
+                    Pat::Ident(pat_ident) => lower_camel_case(&pat_ident.ident.to_string()),
+                    _ => format!("arg{}", params.len()),
+                };
+                let ty = param_type
+                    .clone()
+                    .unwrap_or_else(|| dart_type(&pat_type.ty));
+                params.push((format!("{} {}", ty, name), is_option(&pat_type.ty)));
+            }
+            // Only a trailing run of `Option` parameters can be optional in Dart.
+            let required = params
+                .iter()
+                .rposition(|(_, optional)| !optional)
+                .map_or(0, |last| last + 1);
+            for (_, optional) in &mut params[..required] {
+                *optional = false;
+            }
+
+            let return_type = match &sig.output {
+                ReturnType::Default => "void".to_string(),
+                ReturnType::Type(_, ty) => dart_type(ty),
+            };
+            (
+                lower_camel_case(&sig.ident.to_string()),
+                return_type,
+                params,
+            )
+        };
+
+        let docs = self.docs.join("\n");
+        let dart_name = self.name.clone().unwrap_or(dart_name);
+        let return_type = self.returns.clone().unwrap_or(return_type);
+        let class = match class {
+            Some(class) => quote!(::core::option::Option::Some(#class)),
+            None => quote!(::core::option::Option::None),
+        };
+        let params = params.iter().map(|(declaration, optional)| {
+            quote! {
+                crate::dart_api::NativeParam {
+                    declaration: #declaration,
+                    optional: #optional,
+                }
+            }
+        });
+
+        Ok(quote! {
+            .with_signature(crate::dart_api::NativeSignature {
+                class: #class,
+                dart_name: #dart_name,
+                docs: #docs,
+                return_type: #return_type,
+                params: &[#(#params),*],
+            })
+        })
+    }
+}
+
+fn take_dart_attrs(attrs: &mut Vec<Attribute>) -> Vec<Attribute> {
+    let (dart, rest) = std::mem::take(attrs)
+        .into_iter()
+        .partition(|attr| attr.path().is_ident("dart"));
+    *attrs = rest;
+    dart
+}
+
+/// Dart parameter declarations paired with whether they are optional.
+type DartParams = Vec<(String, bool)>;
+
+/// Splits `Type name(A a, B b, [C c])` into its name, return type and parameters.
+fn parse_dart_signature(signature: &str) -> Option<(String, String, DartParams)> {
+    let open = signature.find('(')?;
+    let close = signature.rfind(')')?;
+    let (return_type, name) = signature[..open].trim().rsplit_once(char::is_whitespace)?;
+
+    let mut params = Vec::new();
+    let mut optional = false;
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in signature[open + 1..close].chars() {
+        match c {
+            '[' if depth == 0 => optional = true,
+            ']' if depth == 0 => {}
+            ',' if depth == 0 => {
+                if !current.trim().is_empty() {
+                    params.push((current.trim().to_string(), optional));
+                }
+                current.clear();
+            }
+            _ => {
+                match c {
+                    '<' | '(' | '{' => depth += 1,
+                    '>' | ')' | '}' => depth -= 1,
+                    _ => {}
+                }
+                current.push(c);
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        params.push((current.trim().to_string(), optional));
+    }
+
+    Some((name.to_string(), return_type.trim().to_string(), params))
+}
+
+fn lower_camel_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut upper = false;
+    for c in name.trim_start_matches('_').chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            result.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn generic_arg(ty: &Type) -> Option<&Type> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let PathArguments::AngleBracketed(args) = &type_path.path.segments.last()?.arguments else {
+        return None;
+    };
+    args.args.iter().find_map(|arg| match arg {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    })
+}
+
+fn is_option(ty: &Type) -> bool {
+    last_ident(ty).is_some_and(|ident| ident == "Option")
+}
+
+/// The Dart type a native parameter or return value shows up as.
+fn dart_type(ty: &Type) -> String {
+    match ty {
+        Type::Reference(type_ref) => dart_type(&type_ref.elem),
+        Type::Tuple(tuple) if tuple.elems.is_empty() => "void".to_string(),
+        _ => {
+            let Some(ident) = last_ident(ty) else {
+                return "dynamic".to_string();
+            };
+            match ident.to_string().as_str() {
+                "bool" => "bool".to_string(),
+                "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" => {
+                    "int".to_string()
+                }
+                "f32" | "f64" => "double".to_string(),
+                "String" | "str" => "String".to_string(),
+                "Handle" => "Object?".to_string(),
+                "TypedDataView" => "TypedData".to_string(),
+                "DartString" => "String".to_string(),
+                "DartInt" => "int".to_string(),
+                "DartList" => "List<Object?>".to_string(),
+                "DartMap" => "Map<Object?, Object?>".to_string(),
+                "DartClosure" => "Function".to_string(),
+                "DartInstance" => "Object".to_string(),
+                "Serde" => "dynamic".to_string(),
+                "Option" => {
+                    let inner = generic_arg(ty).map_or("dynamic".to_string(), dart_type);
+                    if inner.ends_with('?') || inner == "dynamic" || inner == "void" {
+                        inner
+                    } else {
+                        format!("{}?", inner)
+                    }
+                }
+                "Result" => generic_arg(ty).map_or("dynamic".to_string(), dart_type),
+                "Vec" => format!(
+                    "List<{}>",
+                    generic_arg(ty).map_or("dynamic".to_string(), dart_type)
+                ),
+                name => name.to_string(),
+            }
+        }
+    }
+}
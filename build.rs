@@ -9,6 +9,8 @@ fn main() {
     let bindings = bindgen::Builder::default()
         .header("dart_dll/include/dart_dll.h")
         .header("dart_dll/include/dart_api.h")
+        .header("dart_dll/include/dart_native_api.h")
+        .header("dart_dll/include/dart_tools_api.h")
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         .generate()
         .expect("Unable to generate bindings");
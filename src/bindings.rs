@@ -0,0 +1,421 @@
+//! Generates the Dart side of the natives registered with `#[native_func]` and
+//! `#[native_impl]`.
+//!
+//! Every class with natives gets an `abstract base class _<Class>Natives` holding its
+//! `external` declarations in `<name>.g.dart`, a part of the native library `<name>.dart`
+//! of its namespace. The hand-written classes extend it and keep their constructors and
+//! convenience wrappers.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+
+use crate::dart_api::{NativeFunction, NativeLibrary, NativeSignature};
+
+/// dart format's line width.
+const LINE_WIDTH: usize = 80;
+
+#[derive(clap::Args)]
+pub struct BindingsArgs {
+    /// The `lib` directory of the app package. Each native library `package:app/<name>.dart`
+    /// gets its bindings in `<name>.g.dart` next to it.
+    #[clap(long, default_value = "app/lib")]
+    lib: PathBuf,
+    /// Report drift against the checked-in files instead of writing them.
+    #[clap(long)]
+    check: bool,
+}
+
+/// The package whose native libraries get bindings.
+const PACKAGE: &str = "package:app/";
+
+pub fn run(args: BindingsArgs) -> anyhow::Result<()> {
+    let mut problems = Vec::new();
+    for library in inventory::iter::<NativeLibrary>() {
+        let Some(file) = library.uri().strip_prefix(PACKAGE) else {
+            continue;
+        };
+        let path = args.lib.join(file);
+        let out = path.with_extension("g.dart");
+        let generated = generate(library.namespace(), file);
+
+        if args.check {
+            problems.extend(check(&out, &path, library.namespace(), &generated)?);
+            continue;
+        }
+        std::fs::write(&out, generated)
+            .with_context(|| format!("failed to write {}", out.display()))?;
+        println!("Wrote {}", out.display());
+        for function in unsigned_natives(library.namespace()) {
+            eprintln!(
+                "warning: {} has no Dart signature, add #[dart(signature = \"...\")]",
+                function.name()
+            );
+        }
+    }
+
+    if !args.check {
+        return Ok(());
+    }
+    if problems.is_empty() {
+        println!("Native bindings are up to date");
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("{}", problem);
+    }
+    bail!(
+        "native bindings are out of date ({} problems), run `bigfish bindings`",
+        problems.len()
+    )
+}
+
+/// Compares the checked-in bindings `out` of the library at `path` with `generated`, and
+/// the library's own `external` declarations with the natives of `namespace`.
+fn check(out: &Path, path: &Path, namespace: &str, generated: &str) -> anyhow::Result<Vec<String>> {
+    let checked_in = std::fs::read_to_string(out)
+        .with_context(|| format!("failed to read {}", out.display()))?;
+    let library = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+
+    let mut problems = Vec::new();
+    let expected = declarations(generated);
+    let actual = declarations(&checked_in);
+    for (name, declaration) in &expected {
+        match actual.get(name) {
+            None => problems.push(format!("{} is missing from {}", name, out.display())),
+            Some(actual) if actual != declaration => problems.push(format!(
+                "{} changed:\n  expected: {}\n  found:    {}",
+                name,
+                collapse(declaration),
+                collapse(actual)
+            )),
+            Some(_) => {}
+        }
+    }
+    for name in actual.keys().filter(|name| !expected.contains_key(*name)) {
+        problems.push(format!("{} is not a registered native", name));
+    }
+    if problems.is_empty() && generated != checked_in {
+        problems.push(format!("{} is not formatted as generated", out.display()));
+    }
+
+    let registered: BTreeSet<&str> = inventory::iter::<NativeFunction>()
+        .filter(|function| function.namespace() == namespace)
+        .map(|function| function.name())
+        .collect();
+    for name in declarations(&library).keys() {
+        if !registered.contains(name.as_str()) {
+            problems.push(format!(
+                "{} declares {}, which is not a registered native in namespace `{}`",
+                path.display(),
+                name,
+                namespace
+            ));
+        } else if expected.contains_key(name) {
+            problems.push(format!(
+                "{} declares {}, which is already generated",
+                path.display(),
+                name
+            ));
+        }
+    }
+    for function in unsigned_natives(namespace) {
+        problems.push(format!(
+            "{} has no Dart signature, add #[dart(signature = \"...\")]",
+            function.name()
+        ));
+    }
+    Ok(problems)
+}
+
+/// Renders the part of `part_of` for every native in `namespace` with a signature,
+/// sorted so the output doesn't depend on registration order.
+pub fn generate(namespace: &str, part_of: &str) -> String {
+    let mut top_level = Vec::new();
+    let mut classes: BTreeMap<&str, Vec<(&str, &NativeSignature)>> = BTreeMap::new();
+    for function in inventory::iter::<NativeFunction>() {
+        if function.namespace() != namespace {
+            continue;
+        }
+        let Some(signature) = function.signature() else {
+            continue;
+        };
+        match signature.class {
+            Some(class) => classes
+                .entry(class)
+                .or_default()
+                .push((function.name(), signature)),
+            None => top_level.push((function.name(), signature)),
+        }
+    }
+    top_level.sort_by_key(|(name, _)| *name);
+
+    let mut out = String::new();
+    out.push_str("// GENERATED CODE - DO NOT MODIFY BY HAND.\n");
+    out.push_str("// Regenerate with `cargo run -- bindings`.\n\n");
+    let _ = writeln!(out, "part of '{}';", part_of);
+
+    for (name, signature) in top_level {
+        out.push('\n');
+        write_declaration(&mut out, name, signature, "");
+    }
+
+    for (class, mut functions) in classes {
+        functions.sort_by_key(|(name, _)| *name);
+        out.push('\n');
+        let header = format!("abstract base class _{}Natives", class);
+        let extends = "extends NativeFieldWrapperClass1 {";
+        if header.len() + extends.len() < LINE_WIDTH {
+            let _ = writeln!(out, "{} {}", header, extends);
+        } else {
+            let _ = writeln!(out, "{}\n    {}", header, extends);
+        }
+        for (index, (name, signature)) in functions.into_iter().enumerate() {
+            if index > 0 {
+                out.push('\n');
+            }
+            write_declaration(&mut out, name, signature, "  ");
+        }
+        out.push_str("}\n");
+    }
+
+    out
+}
+
+fn write_declaration(out: &mut String, name: &str, signature: &NativeSignature, indent: &str) {
+    for line in signature.docs.lines() {
+        let separator = if line.is_empty() { "" } else { " " };
+        let _ = writeln!(out, "{}///{}{}", indent, separator, line);
+    }
+    let pragma = format!("{}@pragma('vm:external-name', '{}')", indent, name);
+    if pragma.len() <= LINE_WIDTH {
+        let _ = writeln!(out, "{}", pragma);
+    } else {
+        let _ = writeln!(
+            out,
+            "{0}@pragma(\n{0}  'vm:external-name',\n{0}  '{1}',\n{0})",
+            indent, name
+        );
+    }
+
+    let head = format!(
+        "{}external {} {}(",
+        indent, signature.return_type, signature.dart_name
+    );
+    let (optional, required): (Vec<_>, Vec<_>) =
+        signature.params.iter().partition(|param| param.optional);
+    let required: Vec<_> = required.iter().map(|param| param.declaration).collect();
+    let optional: Vec<_> = optional.iter().map(|param| param.declaration).collect();
+
+    let mut inline = required.join(", ");
+    if !optional.is_empty() {
+        if !inline.is_empty() {
+            inline.push_str(", ");
+        }
+        let _ = write!(inline, "[{}]", optional.join(", "));
+    }
+    if head.len() + inline.len() + 2 <= LINE_WIDTH {
+        let _ = writeln!(out, "{}{});", head, inline);
+        return;
+    }
+
+    // Split one parameter per line with trailing commas, like dart format does.
+    out.push_str(&head);
+    if required.is_empty() {
+        out.push('[');
+    }
+    out.push('\n');
+    for (index, param) in required.iter().enumerate() {
+        let opens_optional = index + 1 == required.len() && !optional.is_empty();
+        let _ = writeln!(
+            out,
+            "{}  {},{}",
+            indent,
+            param,
+            if opens_optional { " [" } else { "" }
+        );
+    }
+    for param in &optional {
+        let _ = writeln!(out, "{}  {},", indent, param);
+    }
+    let close = if optional.is_empty() { ")" } else { "])" };
+    let _ = writeln!(out, "{}{};", indent, close);
+}
+
+fn unsigned_natives(namespace: &str) -> impl Iterator<Item = &'static NativeFunction> + '_ {
+    inventory::iter::<NativeFunction>()
+        .filter(move |function| function.namespace() == namespace && function.signature().is_none())
+}
+
+/// Maps each `vm:external-name` in a Dart source to the declaration following it.
+fn declarations(source: &str) -> BTreeMap<String, String> {
+    let mut declarations = BTreeMap::new();
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if !line.starts_with("@pragma(") {
+            continue;
+        }
+        // dart format splits long pragmas over several lines.
+        let mut pragma = line.to_string();
+        while !pragma.ends_with(')') {
+            let Some(next) = lines.next() else {
+                break;
+            };
+            pragma.push_str(next.trim());
+        }
+        let Some(name) = external_name(&pragma) else {
+            continue;
+        };
+
+        let mut declaration = String::new();
+        for line in lines.by_ref() {
+            declaration.push_str(line.trim());
+            declaration.push('\n');
+            if line.trim_end().ends_with(';') {
+                break;
+            }
+        }
+        declarations.insert(name.to_string(), declaration);
+    }
+    declarations
+}
+
+fn external_name(pragma: &str) -> Option<&str> {
+    let rest = pragma.split_once("vm:external-name")?.1;
+    let rest = rest.trim_start_matches(['\'', '"', ',', ' ']);
+    let end = rest.find(['\'', '"'])?;
+    Some(&rest[..end])
+}
+
+/// Joins a multi-line declaration back onto one line for drift messages.
+fn collapse(declaration: &str) -> String {
+    declaration
+        .lines()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace("[ ", "[")
+        .replace("{ ", "{")
+        .replace(", ]", "]")
+        .replace(", }", "}")
+        .replace(", )", ")")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dart_api::NativeParam;
+
+    fn signature(return_type: &'static str, params: &'static [NativeParam]) -> NativeSignature {
+        NativeSignature {
+            class: None,
+            dart_name: "f",
+            docs: "",
+            return_type,
+            params,
+        }
+    }
+
+    fn render(signature: &NativeSignature) -> String {
+        let mut out = String::new();
+        write_declaration(&mut out, "f", signature, "");
+        out
+    }
+
+    #[test]
+    fn optional_params_go_in_brackets() {
+        let params = &[
+            NativeParam {
+                declaration: "int a",
+                optional: false,
+            },
+            NativeParam {
+                declaration: "int? b",
+                optional: true,
+            },
+        ];
+        assert_eq!(
+            render(&signature("int", params)),
+            "@pragma('vm:external-name', 'f')\nexternal int f(int a, [int? b]);\n"
+        );
+    }
+
+    #[test]
+    fn long_declarations_split_one_param_per_line() {
+        let params = &[
+            NativeParam {
+                declaration: "Map<String, List<int>> first",
+                optional: false,
+            },
+            NativeParam {
+                declaration: "Map<String, List<int>>? second",
+                optional: true,
+            },
+        ];
+        assert_eq!(
+            render(&signature("Future<List<int>>", params)),
+            "@pragma('vm:external-name', 'f')\n\
+             external Future<List<int>> f(\n  \
+             Map<String, List<int>> first, [\n  \
+             Map<String, List<int>>? second,\n\
+             ]);\n"
+        );
+    }
+
+    #[test]
+    fn only_optional_params_open_the_bracket_on_the_first_line() {
+        let params = &[NativeParam {
+            declaration: "Map<String, List<Map<String, Object?>>>? descriptorOverrides",
+            optional: true,
+        }];
+        assert_eq!(
+            render(&signature("Future<void>", params)),
+            "@pragma('vm:external-name', 'f')\n\
+             external Future<void> f([\n  \
+             Map<String, List<Map<String, Object?>>>? descriptorOverrides,\n\
+             ]);\n"
+        );
+    }
+
+    #[test]
+    fn declarations_span_lines() {
+        let source = "\
+@pragma(
+  'vm:external-name',
+  'Gpu_create',
+)
+external Gpu create(
+  Map<String, List<int>> options, {
+  required int width,
+  int? height,
+});
+
+@pragma(\"vm:external-name\", \"Gpu_size\")
+external int get size;
+";
+        let declarations = declarations(source);
+        assert_eq!(declarations.len(), 2);
+        assert_eq!(
+            collapse(&declarations["Gpu_create"]),
+            "external Gpu create(Map<String, List<int>> options, {required int width, int? height});"
+        );
+        assert_eq!(declarations["Gpu_size"], "external int get size;\n");
+    }
+
+    #[test]
+    fn external_name_takes_either_quote() {
+        assert_eq!(
+            external_name("@pragma('vm:external-name', 'Window_poll')"),
+            Some("Window_poll")
+        );
+        assert_eq!(
+            external_name("@pragma(\"vm:external-name\",\"Window_poll\")"),
+            Some("Window_poll")
+        );
+        assert_eq!(external_name("@pragma('vm:entry-point')"), None);
+    }
+}
@@ -10,14 +10,22 @@ pub mod sys {
 }
 
 use std::{
+    any::{Any, TypeId},
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
     ffi::{CStr, CString},
     marker::PhantomData,
     mem::MaybeUninit,
-    os::raw::c_void,
+    os::raw::{c_char, c_void},
     ptr,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        mpsc, Arc, Condvar, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
 };
 
-use crate::dart_api::sys::Dart_LookupLibrary;
+use bigfish_macros::native_func;
 
 pub type Result<T> = std::result::Result<T, DartError>;
 
@@ -27,18 +35,243 @@ pub enum DartError {
     NullHandle,
     #[error("dart api error: {0}")]
     Api(String),
+    #[error("compilation error: {0}")]
+    Compilation(String),
+    /// A Dart exception nobody caught, with its `toString()` and stack trace.
+    #[error("unhandled exception: {exception}")]
+    UnhandledException {
+        exception: String,
+        stack_trace: String,
+        handle: ErrorHandle,
+    },
+    /// An error that unwinds the isolate, such as it being killed or shut down.
+    #[error("fatal error: {message}")]
+    Fatal {
+        message: String,
+        handle: ErrorHandle,
+    },
+    #[error("argument `{name}` (#{index}): {source}")]
+    Argument {
+        index: i32,
+        name: &'static str,
+        source: Box<DartError>,
+    },
+    #[error("{function}: {source}")]
+    Native {
+        function: &'static str,
+        source: Box<DartError>,
+    },
+    #[error("panicked: {0}")]
+    Panic(String),
+    #[error("expected a Dart {expected}, found {found}")]
+    Type {
+        expected: &'static str,
+        found: String,
+    },
+    /// The object's native peer is of another Rust type.
+    #[error("expected a {expected} peer, found a {found} peer")]
+    PeerTypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The object never got a native peer, e.g. its constructor didn't run the native.
+    #[error("object has no {expected} peer")]
+    MissingPeer { expected: &'static str },
+    /// The object's native peer was already taken back with [`Handle::take_peer`].
+    #[error("{expected} peer was already finalized")]
+    FinalizedPeer { expected: &'static str },
+    /// The peer is borrowed in a way that conflicts with the access asked for, typically
+    /// by a native further up the stack that called back into Dart.
+    #[error("{name} peer is already borrowed{}", if *.exclusively { " exclusively" } else { "" })]
+    PeerBorrowed {
+        name: &'static str,
+        exclusively: bool,
+    },
 }
 
 impl DartError {
+    /// Attributes `source` to the native argument `name` at position `index`.
+    pub fn argument(index: i32, name: &'static str, source: DartError) -> Self {
+        DartError::Argument {
+            index,
+            name,
+            source: Box::new(source),
+        }
+    }
+
+    /// `handle` isn't the `expected` kind of Dart object.
+    pub fn type_mismatch(expected: &'static str, handle: Handle<'_>) -> Self {
+        DartError::Type {
+            expected,
+            found: handle.type_name(),
+        }
+    }
+
+    /// Attributes `source` to the native function registered as `function`.
+    pub fn native(function: &'static str, source: DartError) -> Self {
+        DartError::Native {
+            function,
+            source: Box::new(source),
+        }
+    }
+
+    /// Turns the payload caught by `catch_unwind` into an error carrying the panic message.
+    pub fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "<non-string panic payload>".to_string(),
+            },
+        };
+        DartError::Panic(message)
+    }
+
     fn from_error_handle(handle: sys::Dart_Handle) -> Self {
         unsafe {
             let msg_ptr = sys::Dart_GetError(handle);
-            if msg_ptr.is_null() {
-                return DartError::Api("<Dart_GetError returned null>".to_string());
+            let msg = if msg_ptr.is_null() {
+                "<Dart_GetError returned null>".to_string()
+            } else {
+                CStr::from_ptr(msg_ptr).to_string_lossy().into_owned()
+            };
+            if sys::Dart_IsUnhandledExceptionError(handle) {
+                // `toString()` runs Dart code, so fall back to the VM's message if it throws.
+                let exception = describe(sys::Dart_ErrorGetException(handle)).unwrap_or(msg);
+                let stack_trace =
+                    describe(sys::Dart_ErrorGetStackTrace(handle)).unwrap_or_default();
+                DartError::UnhandledException {
+                    exception,
+                    stack_trace,
+                    handle: ErrorHandle::new(handle),
+                }
+            } else if sys::Dart_IsCompilationError(handle) {
+                DartError::Compilation(msg)
+            } else if sys::Dart_IsFatalError(handle) {
+                DartError::Fatal {
+                    message: msg,
+                    handle: ErrorHandle::new(handle),
+                }
+            } else {
+                DartError::Api(msg)
+            }
+        }
+    }
+
+    /// The Dart error this error was classified from, looking through the native calls
+    /// and arguments it passed.
+    fn error_handle(&self) -> Option<&ErrorHandle> {
+        match self {
+            DartError::UnhandledException { handle, .. } | DartError::Fatal { handle, .. } => {
+                Some(handle)
+            }
+            DartError::Native { source, .. } | DartError::Argument { source, .. } => {
+                source.error_handle()
+            }
+            _ => None,
+        }
+    }
+
+    /// Displays the error with the native calls it passed through and, for unhandled
+    /// exceptions, the Dart stack trace.
+    pub fn pretty(&self) -> PrettyError<'_> {
+        PrettyError(self)
+    }
+}
+
+/// The Dart error object behind a [`DartError`], kept so that natives hand exceptions
+/// and unwind errors raised by Dart back to the VM as they were.
+///
+/// Only released when dropped in its isolate; anywhere else it is left for the isolate to
+/// free when it shuts down.
+pub struct ErrorHandle {
+    raw: sys::Dart_PersistentHandle,
+    isolate: sys::Dart_Isolate,
+}
+
+// Safety: the handle is only used, or released, in the isolate it belongs to.
+unsafe impl Send for ErrorHandle {}
+unsafe impl Sync for ErrorHandle {}
+
+impl ErrorHandle {
+    unsafe fn new(error: sys::Dart_Handle) -> Self {
+        Self {
+            raw: sys::Dart_NewPersistentHandle(error),
+            isolate: sys::Dart_CurrentIsolate(),
+        }
+    }
+
+    /// A local handle to the error, if its isolate is the current one.
+    fn get(&self) -> Option<sys::Dart_Handle> {
+        unsafe {
+            (!self.raw.is_null() && sys::Dart_CurrentIsolate() == self.isolate)
+                .then(|| sys::Dart_HandleFromPersistent(self.raw))
+        }
+    }
+}
+
+impl std::fmt::Debug for ErrorHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ErrorHandle").finish_non_exhaustive()
+    }
+}
+
+impl Drop for ErrorHandle {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.raw.is_null() && sys::Dart_CurrentIsolate() == self.isolate {
+                sys::Dart_DeletePersistentHandle(self.raw);
+            }
+        }
+    }
+}
+
+/// The result of `Dart_ToString` on `object`, if neither that nor `object` is an error.
+unsafe fn describe(object: sys::Dart_Handle) -> Option<String> {
+    if sys::Dart_IsError(object) || sys::Dart_IsNull(object) {
+        return None;
+    }
+    let string = sys::Dart_ToString(object);
+    if sys::Dart_IsError(string) {
+        return None;
+    }
+    Handle::from_raw(string).to_string_lossy().ok()
+}
+
+/// Multi-line rendering of a [`DartError`], from [`DartError::pretty`].
+pub struct PrettyError<'a>(&'a DartError);
+
+impl std::fmt::Display for PrettyError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut error = self.0;
+        // Rust context first, outermost native call on top.
+        loop {
+            match error {
+                DartError::Native { function, source } => {
+                    writeln!(f, "in native `{}`", function)?;
+                    error = source;
+                }
+                DartError::Argument {
+                    index,
+                    name,
+                    source,
+                } => {
+                    writeln!(f, "reading argument `{}` (#{})", name, index)?;
+                    error = source;
+                }
+                _ => break,
+            }
+        }
+        write!(f, "{}", error)?;
+        if let DartError::UnhandledException { stack_trace, .. } = error {
+            if !stack_trace.is_empty() {
+                write!(f, "\nDart stack trace:")?;
+                for line in stack_trace.lines().filter(|line| !line.is_empty()) {
+                    write!(f, "\n  {}", line)?;
+                }
             }
-            let msg = CStr::from_ptr(msg_ptr).to_string_lossy().into_owned();
-            DartError::Api(msg)
         }
+        Ok(())
     }
 }
 
@@ -70,9 +303,60 @@ pub struct RuntimeConfig {
     pub start_service_isolate: bool,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct IsolateData {
-    library: sys::Dart_Handle,
+/// Per-isolate state handed to `DartDll_LoadScript`. Natives read the user data back
+/// through [`Scope::isolate_data`]; it's freed when the isolate shuts down.
+pub struct IsolateData {
+    /// Namespaces whose natives the isolate may resolve, or `None` for all of them.
+    namespaces: Option<Vec<&'static str>>,
+    /// Values of `String.fromEnvironment` and friends.
+    environment: HashMap<String, String>,
+    /// Persistent class handles by library URI and class name, see [`Scope::class`].
+    classes: RefCell<HashMap<String, HashMap<String, PersistentHandle>>>,
+    /// Shared with [`notify_message`] through [`PENDING_MESSAGES`].
+    messages: Arc<PendingMessages>,
+    user_data: Box<dyn Any + Send>,
+    type_name: &'static str,
+}
+
+impl IsolateData {
+    pub fn new<T: Any + Send>(user_data: T) -> Self {
+        Self {
+            namespaces: None,
+            environment: HashMap::new(),
+            classes: RefCell::new(HashMap::new()),
+            messages: Arc::default(),
+            user_data: Box::new(user_data),
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+
+    /// Restricts the isolate to the natives (and native libraries) of `namespaces`.
+    pub fn with_namespaces(mut self, namespaces: &[&'static str]) -> Self {
+        self.namespaces = Some(namespaces.to_vec());
+        self
+    }
+
+    pub fn with_environment(mut self, environment: HashMap<String, String>) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    fn allows(&self, namespace: &str) -> bool {
+        self.namespaces
+            .as_ref()
+            .is_none_or(|namespaces| namespaces.contains(&namespace))
+    }
+
+    /// The data of the current isolate, if it was created through [`Isolate::load`].
+    fn current<'a>() -> Option<&'a IsolateData> {
+        unsafe {
+            if sys::Dart_CurrentIsolate().is_null() {
+                return None;
+            }
+            let data = sys::DartDll_GetUserIsolateData(sys::Dart_CurrentIsolateData());
+            (data as *const IsolateData).as_ref()
+        }
+    }
 }
 
 impl RuntimeConfig {
@@ -96,31 +380,24 @@ impl Runtime {
         Ok(Self { _priv: () })
     }
 
-    pub fn load_script(&self, script_uri: &CStr, package_config: &CStr) -> Result<Isolate> {
-        let isolate_data_ptr = Box::into_raw(Box::new(IsolateData {
-            library: std::ptr::null_mut(),
-        }));
-        let isolate = unsafe {
-            sys::DartDll_LoadScript(
-                script_uri.as_ptr(),
-                package_config.as_ptr(),
-                isolate_data_ptr as *mut c_void,
-            )
-        };
-        if isolate.is_null() {
-            return Err(DartError::Api(
-                "DartDll_LoadScript returned null isolate".into(),
-            ));
+    /// Loads the main isolate, which may resolve every native. Its messages are handled by
+    /// [`Scope::run_event_loop`] on this thread rather than on the VM's thread pool.
+    ///
+    /// `script_uri` may also name a kernel snapshot (`.dill`), which dart_dll loads without
+    /// running the frontend.
+    pub fn load_script(
+        &self,
+        script_uri: &CStr,
+        package_config: &CStr,
+        data: IsolateData,
+    ) -> Result<Isolate> {
+        let isolate = Isolate::load(script_uri, package_config, data)?;
+        unsafe {
+            sys::Dart_EnterIsolate(isolate.raw);
+            sys::Dart_SetMessageNotifyCallback(Some(notify_message));
+            sys::Dart_ExitIsolate();
         }
-        // {
-        //     let scope = isolate.enter();
-        //     let isolate_data = unsafe { &mut *isolate_data_ptr };
-        //     isolate_data.library = get_library(&scope).unwrap().raw;
-        //     let library = scope.library().unwrap();
-        //     unsafe { sys::Dart_SetNativeResolver(library.raw(), Some(native_resolver), None) };
-        // }
-
-        Ok(Isolate { raw: isolate })
+        Ok(isolate)
     }
 
     pub fn drain_microtask_queue<'i>(&self, scope: &Scope<'i>) -> Result<Handle<'i>> {
@@ -136,58 +413,151 @@ impl Drop for Runtime {
     }
 }
 
-fn get_library<'a>(scope: &Scope<'a>) -> Result<Handle<'a>> {
-    let library = unsafe {
-        let url = scope.new_string("package:app/native.dart").unwrap();
-        Dart_LookupLibrary(url.raw)
-    };
-    if library.is_null() {
-        return Err(DartError::Api(
-            "Dart_LookupLibrary returned null library".into(),
-        ));
+/// Answers `String.fromEnvironment` and friends from the current isolate's
+/// [`IsolateData`]; null lets Dart fall back to the default value.
+unsafe extern "C" fn lookup_environment(name: sys::Dart_Handle) -> sys::Dart_Handle {
+    let mut cstr = MaybeUninit::<*const c_char>::uninit();
+    if !sys::Dart_IsError(sys::Dart_StringToCString(name, cstr.as_mut_ptr())) {
+        let name = CStr::from_ptr(cstr.assume_init()).to_string_lossy();
+        let value = IsolateData::current()
+            .and_then(|data| data.environment.get(name.as_ref()))
+            .and_then(|value| CString::new(value.as_str()).ok());
+        if let Some(value) = value {
+            return sys::Dart_NewStringFromCString(value.as_ptr());
+        }
+    }
+    sys::Dart_Null()
+}
+
+/// Messages the VM has queued for an isolate that haven't been handled yet, counted by
+/// [`notify_message`] from whichever thread queued them.
+#[derive(Default)]
+struct PendingMessages {
+    count: Mutex<usize>,
+    queued: Condvar,
+}
+
+impl PendingMessages {
+    fn push(&self) {
+        *self.count.lock().unwrap() += 1;
+        self.queued.notify_one();
+    }
+
+    /// Takes one message to handle, if any is queued.
+    fn take(&self) -> bool {
+        let mut count = self.count.lock().unwrap();
+        let queued = *count > 0;
+        *count = count.saturating_sub(1);
+        queued
+    }
+
+    fn wait(&self, timeout: Duration) {
+        let count = self.count.lock().unwrap();
+        let _ = self
+            .queued
+            .wait_timeout_while(count, timeout, |count| *count == 0)
+            .unwrap();
+    }
+}
+
+/// The pending messages of the loaded isolates, by isolate. [`notify_message`] runs on
+/// whichever thread queued a message, so it looks them up here rather than in the
+/// isolate's [`IsolateData`], which belongs to the isolate's thread.
+static PENDING_MESSAGES: Mutex<BTreeMap<usize, Arc<PendingMessages>>> = Mutex::new(BTreeMap::new());
+
+unsafe extern "C" fn notify_message(isolate: sys::Dart_Isolate) {
+    let messages = PENDING_MESSAGES
+        .lock()
+        .unwrap()
+        .get(&(isolate as usize))
+        .cloned();
+    if let Some(messages) = messages {
+        messages.push();
     }
-    Ok(Handle {
-        raw: library,
-        _marker: PhantomData,
-    })
 }
 
 /// A Dart isolate created/loaded through the embedding API.
 pub struct Isolate {
     raw: sys::Dart_Isolate,
+    data: *mut IsolateData,
 }
 
+// An isolate can be entered from any thread, as long as only one enters it at a time.
+unsafe impl Send for Isolate {}
+
 impl Isolate {
+    /// Loads `script_uri` into a new isolate that owns `data`. The calling thread must not
+    /// have an isolate entered.
+    pub fn load(script_uri: &CStr, package_config: &CStr, data: IsolateData) -> Result<Self> {
+        let data = Box::into_raw(Box::new(data));
+        let isolate = unsafe {
+            sys::DartDll_LoadScript(
+                script_uri.as_ptr(),
+                package_config.as_ptr(),
+                data as *mut c_void,
+            )
+        };
+        if isolate.is_null() {
+            drop(unsafe { Box::from_raw(data) });
+            return Err(DartError::Api(
+                "DartDll_LoadScript returned null isolate".into(),
+            ));
+        }
+        unsafe {
+            sys::Dart_EnterIsolate(isolate);
+            sys::Dart_SetEnvironmentCallback(Some(lookup_environment));
+            sys::Dart_ExitIsolate();
+        }
+        let messages = unsafe { (*data).messages.clone() };
+        PENDING_MESSAGES
+            .lock()
+            .unwrap()
+            .insert(isolate as usize, messages);
+        Ok(Self { raw: isolate, data })
+    }
+
     /// Creates a scope that Rust won't exit, it will be handled by the Dart VM. Hence we use ManuallyDrop to avoid double drop.
     pub fn current<'i>() -> Result<Scope<'i>> {
         let isolate = unsafe { sys::Dart_CurrentIsolate() };
         if isolate.is_null() {
             return Err(DartError::Api("Dart_CurrentIsolate returned null".into()));
         }
-        Ok(Scope {
-            should_exit_on_drop: false,
-            _marker: PhantomData,
-        })
+        Ok(Scope::new(ScopeExit::None))
     }
 
     pub fn enter(&mut self) -> Scope<'_> {
         unsafe {
             sys::Dart_EnterIsolate(self.raw);
             sys::Dart_EnterScope();
-            Scope {
-                should_exit_on_drop: true,
-                _marker: PhantomData,
-            }
         }
+        Scope::new(ScopeExit::ScopeAndIsolate)
     }
 
     /// Shutdown the isolate. This should be called before dropping the Runtime.
     pub fn shutdown(&mut self) {
         unsafe {
             if !self.raw.is_null() {
+                if sys::Dart_CurrentIsolate() != self.raw {
+                    sys::Dart_EnterIsolate(self.raw);
+                }
+                // The cached classes are persistent handles, deleted in the isolate.
+                (*self.data).classes.get_mut().clear();
+                // So are the handles of async completions the isolate never took.
+                let isolate = self.raw as usize;
+                COMPLETIONS
+                    .lock()
+                    .unwrap()
+                    .retain(|&(owner, _), _| owner != isolate);
                 sys::Dart_ShutdownIsolate();
+                PENDING_MESSAGES
+                    .lock()
+                    .unwrap()
+                    .remove(&(self.raw as usize));
                 // Mark as null to prevent double shutdown in drop
                 self.raw = std::ptr::null_mut();
+                // The VM no longer references the data once the isolate is gone.
+                drop(Box::from_raw(self.data));
+                self.data = std::ptr::null_mut();
             }
         }
     }
@@ -205,25 +575,177 @@ impl Drop for Isolate {
 ///
 /// All [`Handle`] values produced from this scope are only valid until this is dropped.
 pub struct Scope<'i> {
-    should_exit_on_drop: bool,
+    exit: ScopeExit,
+    /// [`live_local_handles`] when the scope was entered, restored when it exits.
+    handles_at_entry: usize,
     _marker: PhantomData<&'i mut ()>,
 }
 
+/// What dropping a [`Scope`] leaves.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScopeExit {
+    /// Nothing: the VM exits the scope, as for the one around a native call.
+    None,
+    /// The API scope, returning to the enclosing one.
+    Scope,
+    /// The API scope and the isolate.
+    ScopeAndIsolate,
+}
+
+thread_local! {
+    /// Local handles handed out by this module in the scopes entered on this thread.
+    /// Only kept in debug builds.
+    static LIVE_LOCAL_HANDLES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The number of local handles created through this module that are still alive, i.e.
+/// whose scope hasn't been exited yet. A count that keeps growing across frames means
+/// per-frame work is missing a [`Scope::with_scope`]. Always 0 in release builds.
+pub fn live_local_handles() -> usize {
+    LIVE_LOCAL_HANDLES.get()
+}
+
 impl<'i> Scope<'i> {
+    fn new(exit: ScopeExit) -> Self {
+        Self {
+            exit,
+            handles_at_entry: live_local_handles(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Enters a nested API scope, exited when the returned scope is dropped. Its handles
+    /// live as long as the borrow of `self` rather than the nested scope, so it's only
+    /// handed out through [`Scope::with_scope`], whose closure they can't escape.
+    fn nested(&mut self) -> Scope<'_> {
+        unsafe { sys::Dart_EnterScope() };
+        Scope::new(ScopeExit::Scope)
+    }
+
+    /// Runs `f` in a nested API scope; the local handles created in it are freed when `f`
+    /// returns. Borrowing `self` mutably keeps the outer scope from creating handles, which
+    /// would land in the nested one, in the meantime.
+    pub fn with_scope<R>(&mut self, f: impl FnOnce(&mut Scope<'_>) -> R) -> R {
+        f(&mut self.nested())
+    }
+
     pub fn library(&self, name: &str) -> Result<Handle<'i>> {
         let url = self.new_string(name)?;
-        let lib = unsafe { sys::Dart_LookupLibrary(url.raw) };
-        if lib.is_null() {
-            return Err(DartError::Api(
-                "Dart_LookupLibrary returned null library".into(),
-            ));
+        self.check(unsafe { sys::Dart_LookupLibrary(url.raw) })
+    }
+
+    /// The library of the script the isolate was loaded from.
+    pub fn root_library(&self) -> Result<Handle<'i>> {
+        self.check(unsafe { sys::Dart_RootLibrary() })
+    }
+
+    /// The class `name` declared in `library`, looked up once and then served from the
+    /// isolate's per-library cache.
+    pub fn class(&self, library: &str, name: &str) -> Result<Handle<'i>> {
+        let Some(data) = IsolateData::current() else {
+            return self.get_class(self.library(library)?, name);
+        };
+        let cached = data
+            .classes
+            .borrow()
+            .get(library)
+            .and_then(|classes| classes.get(name))
+            .map(PersistentHandle::raw);
+        if let Some(class) = cached {
+            return self.check(unsafe { sys::Dart_HandleFromPersistent(class) });
         }
-        Ok(Handle {
-            raw: lib,
-            _marker: PhantomData,
+
+        let class = self.get_class(self.library(library)?, name)?;
+        let persistent = PersistentHandle::new(class)?;
+        data.classes
+            .borrow_mut()
+            .entry(library.to_string())
+            .or_default()
+            .insert(name.to_string(), persistent);
+        Ok(class)
+    }
+
+    /// The user data of the current isolate, as passed to [`IsolateData::new`].
+    pub fn isolate_data<T: Any>(&self) -> Result<&'i T> {
+        let data = IsolateData::current()
+            .ok_or_else(|| DartError::Api("the current isolate has no isolate data".into()))?;
+        data.user_data.downcast_ref().ok_or_else(|| {
+            DartError::Api(format!(
+                "isolate data is a `{}`, not a `{}`",
+                data.type_name,
+                std::any::type_name::<T>()
+            ))
         })
     }
 
+    /// Installs the [`native_resolver`] of its namespace on every registered
+    /// [`NativeLibrary`] that is loaded and returns their URIs. Libraries that aren't
+    /// loaded yet, or whose namespace the isolate's [`IsolateData`] doesn't allow, are
+    /// skipped, so this is run again after a reload.
+    pub fn install_native_resolvers(&self) -> Vec<&'static str> {
+        let data = IsolateData::current();
+        let mut installed = Vec::new();
+        for library in inventory::iter::<NativeLibrary>() {
+            if data.is_some_and(|data| !data.allows(library.namespace)) {
+                continue;
+            }
+            if let Ok(handle) = self.library(library.uri) {
+                self.set_native_resolver(handle, native_resolver(library.namespace));
+                installed.push(library.uri);
+            }
+        }
+        installed
+    }
+
+    /// Installs the native resolvers and compiles every function of the isolate, which
+    /// resolves each native the way its first call would. Returns the natives that failed
+    /// to resolve, each with the reason; the isolate must not run Dart afterwards if there
+    /// are any.
+    pub fn check_natives(&self) -> Result<Vec<String>> {
+        self.install_native_resolvers();
+        RESOLVE_FAILURES.set(Some(Vec::new()));
+        let compiled = self.check(unsafe { sys::Dart_CompileAll() });
+        let failures = RESOLVE_FAILURES.take().unwrap_or_default();
+        compiled?;
+        Ok(failures)
+    }
+
+    /// Runs the isolate's event loop for up to `budget`: drains the microtask queue and
+    /// handles queued messages (timers, ports, async natives) so `Future`s, `Stream`s and
+    /// `await` make progress between frames. Messages still queued when the budget runs
+    /// out are handled on the next call.
+    pub fn run_event_loop(&self, budget: Duration) -> Result<()> {
+        let deadline = Instant::now() + budget;
+        self.drain_microtasks()?;
+        let Some(data) = IsolateData::current() else {
+            return Ok(());
+        };
+        while Instant::now() < deadline && data.messages.take() {
+            // Microtasks scheduled by the message run before this returns.
+            self.check(unsafe { sys::Dart_HandleMessage() })?;
+        }
+        Ok(())
+    }
+
+    /// Blocks for up to `timeout` until a message is queued for the isolate, for loops with
+    /// nothing else to do between turns of [`Scope::run_event_loop`].
+    pub fn wait_for_messages(&self, timeout: Duration) {
+        if let Some(data) = IsolateData::current() {
+            data.messages.wait(timeout);
+        }
+    }
+
+    pub fn drain_microtasks(&self) -> Result<()> {
+        self.check(unsafe { sys::DartDll_DrainMicrotaskQueue() })?;
+        Ok(())
+    }
+
+    /// Whether the isolate has open `ReceivePort`s, such as pending timers or async
+    /// natives, that may still deliver messages.
+    pub fn has_live_ports(&self) -> bool {
+        unsafe { sys::Dart_HasLivePorts() }
+    }
+
     pub fn set_native_resolver(
         &self,
         library: Handle<'i>,
@@ -235,16 +757,111 @@ impl<'i> Scope<'i> {
     #[inline]
     pub fn check(&self, handle: sys::Dart_Handle) -> Result<Handle<'i>> {
         let handle = check(handle)?;
-        Ok(Handle {
-            raw: handle,
-            _marker: PhantomData,
-        })
+        Ok(Handle::from_raw(handle))
     }
 
     pub fn new_string(&self, s: &str) -> Result<Handle<'i>> {
-        let s =
-            CString::new(s).map_err(|_| DartError::Api("string contained interior NUL".into()))?;
-        self.check(unsafe { sys::Dart_NewStringFromCString(s.as_ptr()) })
+        self.new_string_from_utf8(s.as_bytes())
+    }
+
+    /// A string from UTF-8 bytes, which may contain NULs. Invalid UTF-8 is an error.
+    pub fn new_string_from_utf8(&self, utf8: &[u8]) -> Result<Handle<'i>> {
+        self.check(unsafe { sys::Dart_NewStringFromUTF8(utf8.as_ptr(), utf8.len() as isize) })
+    }
+
+    /// A string from UTF-16 code units, which is how Dart stores strings.
+    pub fn new_string_from_utf16(&self, utf16: &[u16]) -> Result<Handle<'i>> {
+        self.check(unsafe { sys::Dart_NewStringFromUTF16(utf16.as_ptr(), utf16.len() as isize) })
+    }
+
+    /// A `List<dynamic>` of `length` nulls.
+    pub fn new_list(&self, length: usize) -> Result<Handle<'i>> {
+        self.check(unsafe { sys::Dart_NewList(length as isize) })
+    }
+
+    /// A list of `length` nulls with elements of `element_type`, which must be nullable.
+    pub fn new_list_of_type(&self, element_type: Handle<'i>, length: usize) -> Result<Handle<'i>> {
+        self.check(unsafe { sys::Dart_NewListOfType(element_type.raw, length as isize) })
+    }
+
+    /// A list of `length` copies of `fill` with elements of `element_type`.
+    pub fn new_list_of_type_filled(
+        &self,
+        element_type: Handle<'i>,
+        fill: Handle<'i>,
+        length: usize,
+    ) -> Result<Handle<'i>> {
+        self.check(unsafe {
+            sys::Dart_NewListOfTypeFilled(element_type.raw, fill.raw, length as isize)
+        })
+    }
+
+    /// An empty `Map<K, V>` with the given key and value types, made through the
+    /// `dart:core` factory constructor since the embedding API has no map constructor.
+    pub fn new_map(&self, key_type: Handle<'i>, value_type: Handle<'i>) -> Result<Handle<'i>> {
+        let map_type = self.non_nullable_type("dart:core", "Map", &[key_type, value_type])?;
+        self.new_object(map_type, self.null_handle()?, &mut [])
+    }
+
+    /// A zero-filled typed data list (`Uint8List`, `Float32List`, ...) of `length` elements.
+    pub fn new_typed_data(&self, kind: TypedDataKind, length: usize) -> Result<Handle<'i>> {
+        self.check(unsafe { sys::Dart_NewTypedData(kind.raw(), length as isize) })
+    }
+
+    /// The non-nullable type `class_name<type_arguments>` declared in `library`.
+    pub fn non_nullable_type(
+        &self,
+        library: &str,
+        class_name: &str,
+        type_arguments: &[Handle<'i>],
+    ) -> Result<Handle<'i>> {
+        let library = self.library(library)?;
+        let class_name = self.new_string(class_name)?;
+        let mut type_arguments: Vec<_> = type_arguments.iter().map(|handle| handle.raw).collect();
+        self.check(unsafe {
+            sys::Dart_GetNonNullableType(
+                library.raw,
+                class_name.raw,
+                type_arguments.len() as isize,
+                if type_arguments.is_empty() {
+                    ptr::null_mut()
+                } else {
+                    type_arguments.as_mut_ptr()
+                },
+            )
+        })
+    }
+
+    /// The `dynamic` type.
+    pub fn dynamic_type(&self) -> Result<Handle<'i>> {
+        self.check(unsafe { sys::Dart_TypeDynamic() })
+    }
+
+    /// A `List<String>` holding `values`.
+    pub fn new_string_list(&self, values: &[String]) -> Result<Handle<'i>> {
+        let string_type = self.non_nullable_type("dart:core", "String", &[])?;
+        let list = self.new_list_of_type_filled(string_type, self.new_string("")?, values.len())?;
+        for (index, value) in values.iter().enumerate() {
+            let value = self.new_string(value)?;
+            self.check(unsafe { sys::Dart_ListSetAt(list.raw, index as isize, value.raw) })?;
+        }
+        Ok(list)
+    }
+
+    /// Starts `name` of `library` the way the standalone VM starts `main`: through
+    /// `dart:isolate`'s `_startMainIsolate`, which passes `args` only if the function takes
+    /// them. The function runs on the next turn of the event loop.
+    pub fn start_entry_point(
+        &mut self,
+        library: Handle<'i>,
+        name: &str,
+        args: &[String],
+    ) -> Result<()> {
+        let entry_point = library.get_field(self.new_string(name)?)?;
+        let isolate_library = self.library("dart:isolate")?;
+        let mut args = [entry_point.raw, self.new_string_list(args)?.raw];
+        self.invoke(isolate_library, "_startMainIsolate", &mut args)?;
+        Ok(())
     }
 
     pub fn invoke(
@@ -264,6 +881,44 @@ impl<'i> Scope<'i> {
         self.check(unsafe { sys::Dart_GetClass(library.raw, class_name.raw) })
     }
 
+    /// Exposes `length` elements at `data` to Dart as a typed list (`Uint8List` for `u8`,
+    /// `Float32List` for `f32`, ...) without copying, so Dart reads and writes the storage
+    /// in place. `owner` keeps the storage alive and is dropped once Dart collects the list,
+    /// by a finalizer that may run on any thread.
+    ///
+    /// # Safety
+    ///
+    /// `data` must point to `length` elements that stay valid and don't move for as long
+    /// as `owner` is alive.
+    pub unsafe fn new_external_typed_data<T: TypedDataElement, O: Send + 'static>(
+        &self,
+        data: *mut T,
+        length: usize,
+        owner: O,
+    ) -> Result<Handle<'i>> {
+        unsafe extern "C" fn finalizer<O>(_isolate_callback_data: *mut c_void, peer: *mut c_void) {
+            drop(Box::from_raw(peer as *mut O));
+        }
+        let owner = Box::into_raw(Box::new(owner));
+        let list = sys::Dart_NewExternalTypedDataWithFinalizer(
+            T::KIND.raw(),
+            data as *mut c_void,
+            length as isize,
+            owner as *mut c_void,
+            // The list holds on to the whole storage, so the GC should weigh it.
+            (length * std::mem::size_of::<T>()) as isize,
+            Some(finalizer::<O>),
+        );
+        if sys::Dart_IsError(list) {
+            drop(Box::from_raw(owner));
+        }
+        self.check(list)
+    }
+
+    pub fn new_send_port(&self, port: SendPort) -> Result<Handle<'i>> {
+        self.check(unsafe { sys::Dart_NewSendPort(port.0) })
+    }
+
     pub fn new_double(&self, value: f64) -> Result<Handle<'i>> {
         self.check(unsafe { sys::Dart_NewDouble(value) })
     }
@@ -295,16 +950,49 @@ impl<'i> Scope<'i> {
             )
         })
     }
+
+    /// Builds the Dart exception object that represents `error`.
+    ///
+    /// Argument errors become an `ArgumentError` naming the parameter, everything else
+    /// an `Exception` carrying the message.
+    pub fn new_exception(&self, error: &DartError) -> Result<Handle<'i>> {
+        let argument_error = |message: String, name: &str| {
+            let class = self.class("dart:core", "ArgumentError")?;
+            let message = self.new_string(&message)?;
+            let name = self.new_string(name)?;
+            self.new_object(class, self.null_handle()?, &mut [message.raw, name.raw])
+        };
+        match error {
+            DartError::Argument { name, source, .. } => argument_error(source.to_string(), name),
+            DartError::Native { function, source } => match source.as_ref() {
+                DartError::Argument { name, source, .. } => {
+                    argument_error(format!("{}: {}", function, source), name)
+                }
+                _ => self.new_exception_with_message(&error.to_string()),
+            },
+            _ => self.new_exception_with_message(&error.to_string()),
+        }
+    }
+
+    fn new_exception_with_message(&self, message: &str) -> Result<Handle<'i>> {
+        let class = self.class("dart:core", "Exception")?;
+        let message = self.new_string(message)?;
+        self.new_object(class, self.null_handle()?, &mut [message.raw])
+    }
 }
 
 impl Drop for Scope<'_> {
     fn drop(&mut self) {
+        if self.exit == ScopeExit::None {
+            return;
+        }
         unsafe {
-            if self.should_exit_on_drop {
-                sys::Dart_ExitScope();
+            sys::Dart_ExitScope();
+            if self.exit == ScopeExit::ScopeAndIsolate {
                 sys::Dart_ExitIsolate();
             }
         }
+        LIVE_LOCAL_HANDLES.set(self.handles_at_entry);
     }
 }
 
@@ -324,6 +1012,9 @@ impl<'s> Clone for Handle<'s> {
 
 impl<'s> Handle<'s> {
     pub(crate) fn from_raw(raw: sys::Dart_Handle) -> Self {
+        if cfg!(debug_assertions) {
+            LIVE_LOCAL_HANDLES.set(LIVE_LOCAL_HANDLES.get() + 1);
+        }
         Self {
             raw,
             _marker: PhantomData,
@@ -358,9 +1049,9 @@ impl<'s> Handle<'s> {
         unsafe { sys::Dart_IsMap(self.raw) }
     }
 
-    pub fn map_keys(self, scope: &Scope<'s>) -> Result<List<'s>> {
+    pub fn map_keys(self, scope: &Scope<'s>) -> Result<DartList<'s>> {
         let keys = scope.check(unsafe { sys::Dart_MapKeys(self.raw) })?;
-        List::new(keys)
+        DartList::try_from(keys)
     }
 
     pub fn map_get(self, scope: &Scope<'s>, key: Handle<'s>) -> Result<Handle<'s>> {
@@ -469,37 +1160,123 @@ impl<'s> Handle<'s> {
         unsafe { sys::Dart_IsClosure(self.raw) }
     }
 
-    pub fn set_peer<T>(self, peer: Box<T>) {
-        let peer_ptr = Box::into_raw(peer);
-        unsafe { sys::Dart_SetPeer(self.raw, peer_ptr as *mut c_void) };
+    /// Whether this is a Dart object other than null.
+    pub fn is_instance(self) -> bool {
+        !self.is_null() && unsafe { sys::Dart_IsInstance(self.raw) }
+    }
+
+    /// The name of the object's runtime type, for error messages.
+    pub fn type_name(self) -> String {
+        if self.is_null() {
+            return "Null".to_string();
+        }
         unsafe {
-            unsafe extern "C" fn finalizer<T>(
-                _isolate_callback_data: *mut c_void,
-                peer: *mut c_void,
-            ) {
-                let boxed = Box::from_raw(peer as *mut T);
-                drop(boxed);
+            let runtime_type = sys::Dart_InstanceGetType(self.raw);
+            let name = sys::Dart_ToString(runtime_type);
+            if sys::Dart_IsError(runtime_type) || sys::Dart_IsError(name) {
+                return "<unknown>".to_string();
             }
-            sys::Dart_NewFinalizableHandle(
-                self.raw,
-                peer_ptr as *mut c_void,
-                std::mem::size_of::<T>() as isize,
-                Some(finalizer::<T>),
-            )
+            Handle::from_raw(name)
+                .to_string_lossy()
+                .unwrap_or_else(|_| "<unknown>".to_string())
+        }
+    }
+
+    pub fn set_peer<T: Send + 'static>(self, peer: Box<T>) -> Result<()> {
+        self.set_peer_with_size(peer, std::mem::size_of::<T>())
+    }
+
+    /// Like [`Handle::set_peer`], reporting `external_size` bytes of native memory held by
+    /// the peer to the GC.
+    pub fn set_peer_with_size<T: Send + 'static>(
+        self,
+        peer: Box<T>,
+        external_size: usize,
+    ) -> Result<()> {
+        let cell = Box::new(PeerCell {
+            tag: PeerTag::of::<T>(),
+            finalizable: ptr::null_mut(),
+            borrows: Cell::new(0),
+            value: peer,
+        });
+        let handle = FinalizableHandle::new(self, cell, external_size)?;
+        unsafe { (*handle.peer).finalizable = handle.raw };
+        check(unsafe { sys::Dart_SetPeer(self.raw, handle.peer as *mut c_void) })?;
+        Ok(())
+    }
+
+    /// Borrows the peer set with [`Handle::set_peer`], checked to be a `T`. Any number of
+    /// shared borrows may be live at once, but none alongside [`Handle::peer_mut`].
+    pub fn peer<T: 'static>(self) -> Result<PeerRef<'s, T>> {
+        let cell = self.peer_cell::<T>()?;
+        let borrows = unsafe { &(*cell).borrows };
+        if borrows.get() < 0 {
+            return Err(DartError::PeerBorrowed {
+                name: std::any::type_name::<T>(),
+                exclusively: true,
+            });
+        }
+        borrows.set(borrows.get() + 1);
+        Ok(PeerRef {
+            cell,
+            _scope: PhantomData,
+        })
+    }
+
+    /// Borrows the peer exclusively, failing while any other borrow of it is live.
+    pub fn peer_mut<T: 'static>(self) -> Result<PeerMut<'s, T>> {
+        let cell = self.peer_cell::<T>()?;
+        let borrows = unsafe { &(*cell).borrows };
+        if borrows.get() != 0 {
+            return Err(DartError::PeerBorrowed {
+                name: std::any::type_name::<T>(),
+                exclusively: borrows.get() < 0,
+            });
+        }
+        borrows.set(-1);
+        Ok(PeerMut {
+            cell,
+            _scope: PhantomData,
+        })
+    }
+
+    /// Detaches the peer from the object and hands it back, so it is dropped now rather
+    /// than when the object is collected. Later lookups report [`DartError::FinalizedPeer`].
+    pub fn take_peer<T: 'static>(self) -> Result<Box<T>> {
+        let cell = self.peer_cell::<T>()?;
+        if unsafe { (*cell).borrows.get() } != 0 {
+            return Err(DartError::PeerBorrowed {
+                name: std::any::type_name::<T>(),
+                exclusively: unsafe { (*cell).borrows.get() } < 0,
+            });
+        }
+        check(unsafe { sys::Dart_SetPeer(self.raw, FINALIZED_PEER.as_ptr() as *mut c_void) })?;
+        let handle = FinalizableHandle {
+            raw: unsafe { (*cell).finalizable },
+            peer: cell,
         };
+        Ok(unsafe { handle.cancel(self) }.value)
     }
 
-    pub fn get_peer<'a, T>(self) -> Result<&'a mut T> {
-        let mut peer = MaybeUninit::<*mut T>::uninit();
-        check(unsafe { sys::Dart_GetPeer(self.raw, peer.as_mut_ptr() as *mut *mut c_void) })?;
-        if unsafe { *peer.as_mut_ptr() }.is_null() {
-            // Use std::any::type_name to print the type at debug time
-            return Err(DartError::Api(format!(
-                "Dart_GetPeer returned null for type {}",
-                std::any::type_name::<T>()
-            )));
+    fn peer_cell<T: 'static>(self) -> Result<*mut PeerCell<T>> {
+        let expected = std::any::type_name::<T>();
+        let mut peer: *mut c_void = ptr::null_mut();
+        check(unsafe { sys::Dart_GetPeer(self.raw, &mut peer) })?;
+        if peer.is_null() {
+            return Err(DartError::MissingPeer { expected });
+        }
+        if ptr::eq(peer as *const u8, FINALIZED_PEER.as_ptr()) {
+            return Err(DartError::FinalizedPeer { expected });
+        }
+        // Every cell starts with its tag, whatever the value's type.
+        let tag = unsafe { &*(peer as *const PeerTag) };
+        if tag.type_id != TypeId::of::<T>() {
+            return Err(DartError::PeerTypeMismatch {
+                expected,
+                found: tag.type_name,
+            });
         }
-        Ok(unsafe { &mut *peer.assume_init() })
+        Ok(peer as *mut PeerCell<T>)
     }
 
     pub fn set_field(self, name: Handle<'s>, value: &Handle<'s>) {
@@ -511,20 +1288,14 @@ impl<'s> Handle<'s> {
         if value.is_null() {
             return Err(DartError::NullHandle);
         }
-        Ok(Handle {
-            raw: value,
-            _marker: PhantomData,
-        })
+        Ok(Handle::from_raw(value))
     }
 
     pub fn invoke(self, name: Handle<'s>, args: &mut [sys::Dart_Handle]) -> Result<Handle<'s>> {
         let handle =
             unsafe { sys::Dart_Invoke(self.raw, name.raw, args.len() as i32, args.as_mut_ptr()) };
         check(handle)?;
-        Ok(Handle {
-            raw: handle,
-            _marker: PhantomData,
-        })
+        Ok(Handle::from_raw(handle))
     }
 }
 
@@ -564,6 +1335,11 @@ impl PersistentHandle {
     pub fn raw(&self) -> sys::Dart_PersistentHandle {
         self.raw
     }
+
+    /// A local handle to the object, valid for `scope`.
+    pub fn get<'s>(&self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        scope.check(unsafe { sys::Dart_HandleFromPersistent(self.raw) })
+    }
 }
 
 impl Drop for PersistentHandle {
@@ -574,6 +1350,404 @@ impl Drop for PersistentHandle {
     }
 }
 
+/// A handle that doesn't keep its object alive, for caches of Dart objects. It reads as
+/// `None` once the object has been collected.
+pub struct WeakPersistentHandle {
+    raw: sys::Dart_WeakPersistentHandle,
+}
+
+// Safety: as for `PersistentHandle`.
+unsafe impl Send for WeakPersistentHandle {}
+unsafe impl Sync for WeakPersistentHandle {}
+
+impl WeakPersistentHandle {
+    pub fn new(handle: Handle<'_>) -> Result<Self> {
+        // The VM requires a callback even when there is no peer to clean up.
+        unsafe extern "C" fn finalizer(_isolate_callback_data: *mut c_void, _peer: *mut c_void) {}
+        let weak = unsafe {
+            sys::Dart_NewWeakPersistentHandle(handle.raw, ptr::null_mut(), 0, Some(finalizer))
+        };
+        if weak.is_null() {
+            return Err(DartError::NullHandle);
+        }
+        Ok(Self { raw: weak })
+    }
+
+    /// A local handle to the object, or `None` if it has been collected.
+    pub fn get<'s>(&self, scope: &Scope<'s>) -> Result<Option<Handle<'s>>> {
+        let handle = scope.check(unsafe { sys::Dart_HandleFromWeakPersistent(self.raw) })?;
+        Ok((!handle.is_null()).then_some(handle))
+    }
+}
+
+impl Drop for WeakPersistentHandle {
+    fn drop(&mut self) {
+        unsafe { sys::Dart_DeleteWeakPersistentHandle(self.raw) };
+    }
+}
+
+/// Identifies the Rust type of a native peer.
+struct PeerTag {
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+impl PeerTag {
+    fn of<T: 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+}
+
+/// What [`Handle::set_peer`] hands the VM: the value behind its type tag, so a peer can
+/// be checked before it is cast.
+#[repr(C)]
+struct PeerCell<T> {
+    tag: PeerTag,
+    finalizable: sys::Dart_FinalizableHandle,
+    /// Live shared borrows, or -1 while borrowed exclusively.
+    borrows: Cell<isize>,
+    value: Box<T>,
+}
+
+// Safety: the borrow count and handle are only used on the isolate's thread; elsewhere,
+// the cell is only dropped by the finalizer.
+unsafe impl<T: Send> Send for PeerCell<T> {}
+
+/// A shared borrow of a native peer, from [`Handle::peer`].
+///
+/// The local handle the borrow came from keeps the object, and so the peer, alive for
+/// the scope `'s`.
+pub struct PeerRef<'s, T> {
+    cell: *mut PeerCell<T>,
+    _scope: PhantomData<&'s T>,
+}
+
+impl<T> std::ops::Deref for PeerRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &(*self.cell).value }
+    }
+}
+
+impl<T> Drop for PeerRef<'_, T> {
+    fn drop(&mut self) {
+        let borrows = unsafe { &(*self.cell).borrows };
+        borrows.set(borrows.get() - 1);
+    }
+}
+
+/// An exclusive borrow of a native peer, from [`Handle::peer_mut`].
+pub struct PeerMut<'s, T> {
+    cell: *mut PeerCell<T>,
+    _scope: PhantomData<&'s mut T>,
+}
+
+impl<T> std::ops::Deref for PeerMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &(*self.cell).value }
+    }
+}
+
+impl<T> std::ops::DerefMut for PeerMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut (*self.cell).value }
+    }
+}
+
+impl<T> Drop for PeerMut<'_, T> {
+    fn drop(&mut self) {
+        unsafe { (*self.cell).borrows.set(0) };
+    }
+}
+
+/// Stands in for the peer of objects whose peer was taken back.
+static FINALIZED_PEER: [u8; 1] = [0];
+
+/// Drops a native peer once its Dart object is collected.
+///
+/// Dropping the `FinalizableHandle` leaves the finalizer in place; the peer belongs to
+/// the Dart object from then on.
+pub struct FinalizableHandle<T: 'static> {
+    raw: sys::Dart_FinalizableHandle,
+    peer: *mut T,
+}
+
+impl<T: 'static> FinalizableHandle<T> {
+    /// Attaches `peer` to `object`. `external_size` is the native memory the peer holds,
+    /// which the GC counts towards the heap so large resources get collected in time.
+    ///
+    /// The VM may run the finalizer, which drops the peer, on any thread.
+    pub fn new(object: Handle<'_>, peer: Box<T>, external_size: usize) -> Result<Self>
+    where
+        T: Send,
+    {
+        unsafe extern "C" fn finalizer<T>(_isolate_callback_data: *mut c_void, peer: *mut c_void) {
+            drop(Box::from_raw(peer as *mut T));
+        }
+        let peer = Box::into_raw(peer);
+        let raw = unsafe {
+            sys::Dart_NewFinalizableHandle(
+                object.raw,
+                peer as *mut c_void,
+                external_size as isize,
+                Some(finalizer::<T>),
+            )
+        };
+        if raw.is_null() {
+            drop(unsafe { Box::from_raw(peer) });
+            return Err(DartError::NullHandle);
+        }
+        Ok(Self { raw, peer })
+    }
+
+    /// Removes the finalizer and hands the peer back.
+    ///
+    /// # Safety
+    ///
+    /// `object` must be the object the handle was created for, which proves the finalizer
+    /// hasn't run yet.
+    pub unsafe fn cancel(self, object: Handle<'_>) -> Box<T> {
+        sys::Dart_DeleteFinalizableHandle(self.raw, object.raw);
+        Box::from_raw(self.peer)
+    }
+}
+
+/// The id of a Dart port that any thread can post messages to. Messages to a
+/// `ReceivePort` are delivered on its isolate's thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SendPort(sys::Dart_Port);
+
+impl SendPort {
+    /// Reads the id of a Dart `SendPort`.
+    pub fn from_handle(handle: Handle<'_>) -> Result<Self> {
+        let mut id = 0;
+        check(unsafe { sys::Dart_SendPortGetId(handle.raw, &mut id) })?;
+        Ok(Self(id))
+    }
+
+    pub fn id(self) -> sys::Dart_Port {
+        self.0
+    }
+
+    /// Posts `message`, which the VM copies before returning. Returns `false` if the port
+    /// is closed.
+    pub fn post(self, message: &PortMessage) -> bool {
+        let mut arena = CObjectArena::default();
+        let mut object = arena.encode(message);
+        unsafe { sys::Dart_PostCObject(self.0, &mut object) }
+    }
+
+    pub fn post_integer(self, value: i64) -> bool {
+        unsafe { sys::Dart_PostInteger(self.0, value) }
+    }
+}
+
+impl<'s> IntoDart<'s> for SendPort {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        scope.new_send_port(self)
+    }
+}
+
+/// A value posted to a Dart port, received as the matching Dart object.
+#[derive(Debug, Clone)]
+pub enum PortMessage {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    String(String),
+    /// Received as a `Uint8List`.
+    Bytes(Vec<u8>),
+    List(Vec<PortMessage>),
+}
+
+/// Owns the strings and arrays a `Dart_CObject` tree points into until it is posted.
+#[derive(Default)]
+struct CObjectArena {
+    strings: Vec<CString>,
+    arrays: Vec<Box<[sys::Dart_CObject]>>,
+    pointers: Vec<Box<[*mut sys::Dart_CObject]>>,
+}
+
+impl CObjectArena {
+    fn encode(&mut self, message: &PortMessage) -> sys::Dart_CObject {
+        // Safety: every union field read by the VM is the one matching `type_`.
+        let mut object: sys::Dart_CObject = unsafe { std::mem::zeroed() };
+        match message {
+            PortMessage::Null => object.type_ = sys::Dart_CObject_Type_Dart_CObject_kNull,
+            PortMessage::Bool(value) => {
+                object.type_ = sys::Dart_CObject_Type_Dart_CObject_kBool;
+                object.value.as_bool = *value;
+            }
+            PortMessage::Int(value) => {
+                object.type_ = sys::Dart_CObject_Type_Dart_CObject_kInt64;
+                object.value.as_int64 = *value;
+            }
+            PortMessage::Double(value) => {
+                object.type_ = sys::Dart_CObject_Type_Dart_CObject_kDouble;
+                object.value.as_double = *value;
+            }
+            PortMessage::String(value) => {
+                // Dart strings can hold NULs, C strings can't; cut at the first one.
+                let end = value.find('\0').unwrap_or(value.len());
+                let string = CString::new(&value[..end]).expect("NULs were cut off");
+                object.type_ = sys::Dart_CObject_Type_Dart_CObject_kString;
+                object.value.as_string = string.as_ptr();
+                self.strings.push(string);
+            }
+            PortMessage::Bytes(bytes) => {
+                object.type_ = sys::Dart_CObject_Type_Dart_CObject_kTypedData;
+                object.value.as_typed_data.type_ = sys::Dart_TypedData_Type_Dart_TypedData_kUint8;
+                object.value.as_typed_data.length = bytes.len() as isize;
+                object.value.as_typed_data.values = bytes.as_ptr();
+            }
+            PortMessage::List(values) => {
+                let mut array: Box<[sys::Dart_CObject]> =
+                    values.iter().map(|value| self.encode(value)).collect();
+                let pointers: Box<[*mut sys::Dart_CObject]> =
+                    array.iter_mut().map(|value| value as *mut _).collect();
+                object.type_ = sys::Dart_CObject_Type_Dart_CObject_kArray;
+                object.value.as_array.length = pointers.len() as isize;
+                object.value.as_array.values = pointers.as_ptr() as *mut _;
+                self.arrays.push(array);
+                self.pointers.push(pointers);
+            }
+        }
+        object
+    }
+}
+
+/// A port owned by Rust. Messages Dart sends to it are handed to `handler` on a VM
+/// thread pool thread, not on any isolate. Closed on drop.
+pub struct NativePort {
+    port: SendPort,
+}
+
+impl NativePort {
+    pub fn new(name: &str, handler: sys::Dart_NativeMessageHandler) -> Result<Self> {
+        let name =
+            CString::new(name).map_err(|_| DartError::Api("port name contained NUL".into()))?;
+        let id = unsafe { sys::Dart_NewNativePort(name.as_ptr(), handler, false) };
+        // ILLEGAL_PORT
+        if id == 0 {
+            return Err(DartError::Api("Dart_NewNativePort failed".into()));
+        }
+        Ok(Self { port: SendPort(id) })
+    }
+
+    /// The port to hand to Dart, e.g. as a native's return value.
+    pub fn send_port(&self) -> SendPort {
+        self.port
+    }
+}
+
+impl Drop for NativePort {
+    fn drop(&mut self) {
+        unsafe { sys::Dart_CloseNativePort(self.port.0) };
+    }
+}
+
+type Completion = Box<dyn for<'s> FnOnce(&Scope<'s>) -> Result<Handle<'s>> + Send>;
+
+/// Finished async work waiting for its isolate to pick it up, by isolate and token. An
+/// isolate's leftovers are dropped when it shuts down, see [`Isolate::shutdown`].
+static COMPLETIONS: Mutex<BTreeMap<(usize, i64), Completion>> = Mutex::new(BTreeMap::new());
+static NEXT_TOKEN: AtomicI64 = AtomicI64::new(0);
+
+/// Most threads running the work of [`spawn_async`].
+const MAX_ASYNC_WORKERS: usize = 8;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Queues `job` for the async worker threads, which are started on first use.
+fn run_on_worker(job: Job) -> Result<()> {
+    static WORKERS: OnceLock<Option<mpsc::Sender<Job>>> = OnceLock::new();
+    let workers = WORKERS.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let count = std::thread::available_parallelism()
+            .map_or(1, |count| count.get())
+            .min(MAX_ASYNC_WORKERS);
+        let mut spawned = 0;
+        for index in 0..count {
+            let receiver = receiver.clone();
+            let worker = std::thread::Builder::new()
+                .name(format!("async-worker-{}", index))
+                .spawn(move || loop {
+                    // Only lock while waiting, so the other workers can take the next job.
+                    let job = receiver.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                });
+            if worker.is_ok() {
+                spawned += 1;
+            }
+        }
+        (spawned > 0).then_some(sender)
+    });
+    workers
+        .as_ref()
+        .and_then(|workers| workers.send(job).ok())
+        .ok_or_else(|| DartError::Api("failed to start the async worker threads".into()))
+}
+
+/// Runs `work` on one of the async worker threads, queued while they are all busy, then
+/// `complete` with its output on the isolate thread, finishing the Dart future listening
+/// on `port` with the returned value. Errors and panics from either complete the future
+/// with an exception instead.
+///
+/// `port` is the `SendPort` of the `runAsync` helper in `native.dart`, which is posted a
+/// token once `work` is done and takes the result with `_takeAsyncResult`.
+pub fn spawn_async<T, W, C>(port: SendPort, work: W, complete: C) -> Result<()>
+where
+    T: Send + 'static,
+    W: FnOnce() -> Result<T> + Send + 'static,
+    C: for<'s> FnOnce(&Scope<'s>, T) -> Result<Handle<'s>> + Send + 'static,
+{
+    let isolate = unsafe { sys::Dart_CurrentIsolate() } as usize;
+    run_on_worker(Box::new(move || {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(work))
+            .unwrap_or_else(|payload| Err(DartError::from_panic(payload)));
+        // `complete` may own handles, which can only be released on the isolate thread,
+        // so it's dropped there even when the work failed.
+        let completion: Completion = Box::new(move |scope| complete(scope, result?));
+
+        let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+        COMPLETIONS
+            .lock()
+            .unwrap()
+            .insert((isolate, token), completion);
+        if !port.post_integer(token) {
+            // The isolate is gone, and with it any handles the completion holds, so they
+            // can't be released either.
+            if let Some(completion) = COMPLETIONS.lock().unwrap().remove(&(isolate, token)) {
+                std::mem::forget(completion);
+            }
+        }
+    }))
+}
+
+/// Runs the completion of the async work posted as `token`, returning its Dart result.
+#[native_func]
+#[dart(name = "_takeAsyncResult")]
+fn take_async_result<'s>(scope: &Scope<'s>, token: i64) -> Result<Handle<'s>> {
+    let isolate = unsafe { sys::Dart_CurrentIsolate() } as usize;
+    let completion = COMPLETIONS
+        .lock()
+        .unwrap()
+        .remove(&(isolate, token))
+        .ok_or_else(|| DartError::Api(format!("no async result for token {}", token)))?;
+    completion(scope)
+}
+
 /// Safe wrapper for Dart_NativeArguments
 pub struct NativeArguments<'a> {
     raw: sys::Dart_NativeArguments,
@@ -598,20 +1772,14 @@ impl<'a> NativeArguments<'a> {
 
     pub fn get_arg(&self, index: i32) -> Result<Handle<'a>> {
         let handle = check(unsafe { sys::Dart_GetNativeArgument(self.raw, index) })?;
-        Ok(Handle {
-            raw: handle,
-            _marker: PhantomData,
-        })
+        Ok(Handle::from_raw(handle))
     }
 
     pub fn get_string_arg(&self, index: i32) -> Result<Handle<'a>> {
         let mut peer: *mut c_void = ptr::null_mut();
         let handle =
             check(unsafe { sys::Dart_GetNativeStringArgument(self.raw, index, &mut peer) })?;
-        Ok(Handle {
-            raw: handle,
-            _marker: PhantomData,
-        })
+        Ok(Handle::from_raw(handle))
     }
 
     pub fn get_integer_arg(&self, index: i32) -> Result<i64> {
@@ -670,24 +1838,253 @@ impl<'a> NativeArguments<'a> {
     pub fn set_double_return_value(&self, val: f64) {
         unsafe { sys::Dart_SetDoubleReturnValue(self.raw, val) }
     }
+
+    /// Makes the native call throw `error` in Dart, prefixed with the `function` name.
+    ///
+    /// The exception is handed back as an error return value, which the VM raises once
+    /// the native function has returned. `Dart_ThrowException` and `Dart_PropagateError`
+    /// would instead longjmp over the Rust frames still on the stack, skipping their
+    /// destructors.
+    ///
+    /// Errors raised by Dart, such as exceptions thrown by callbacks or the isolate being
+    /// killed, are handed back unchanged.
+    pub fn set_error(&self, function: &'static str, error: DartError) {
+        if let Some(error_handle) = error.error_handle().and_then(ErrorHandle::get) {
+            unsafe { sys::Dart_SetReturnValue(self.raw, error_handle) };
+            return;
+        }
+        let error = DartError::native(function, error);
+        let exception = Isolate::current().and_then(|scope| scope.new_exception(&error));
+        let error_handle = match exception {
+            Ok(exception) => unsafe { sys::Dart_NewUnhandledExceptionError(exception.raw) },
+            Err(_) => {
+                let message = CString::new(error.to_string().replace('\0', "")).unwrap_or_default();
+                unsafe { sys::Dart_NewApiError(message.as_ptr()) }
+            }
+        };
+        unsafe { sys::Dart_SetReturnValue(self.raw, error_handle) }
+    }
+}
+
+/// Runs the body of a native function shim.
+///
+/// Errors returned by `body`, as well as panics, are thrown in Dart instead of unwinding
+/// into the VM, which would abort the process.
+#[doc(hidden)]
+pub fn call_native(
+    raw: sys::Dart_NativeArguments,
+    function: &'static str,
+    body: impl for<'a> FnOnce(NativeArguments<'a>, Scope<'a>) -> Result<()>,
+) {
+    let handles = live_local_handles();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        body(NativeArguments::from_raw(raw), Isolate::current()?)
+    }))
+    .unwrap_or_else(|payload| Err(DartError::from_panic(payload)));
+
+    if let Err(error) = result {
+        NativeArguments::from_raw(raw).set_error(function, error);
+    }
+    // The VM exits the native call's scope on return.
+    LIVE_LOCAL_HANDLES.set(handles);
+}
+
+/// A value that a typed native can take as a parameter, read directly from the
+/// native arguments without going through serde.
+pub trait FromDartArg<'a>: Sized {
+    fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self>;
+}
+
+impl<'a> FromDartArg<'a> for i64 {
+    fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self> {
+        args.get_integer_arg(index)
+    }
+}
+
+macro_rules! impl_from_dart_arg_int {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a> FromDartArg<'a> for $ty {
+                fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self> {
+                    let value = args.get_integer_arg(index)?;
+                    <$ty>::try_from(value).map_err(|_| {
+                        DartError::Api(format!(
+                            "{} is out of range for {}",
+                            value,
+                            stringify!($ty)
+                        ))
+                    })
+                }
+            }
+        )*
+    };
 }
 
-pub struct List<'s>(Handle<'s>);
+impl_from_dart_arg_int!(i8, i16, i32, isize, u8, u16, u32, u64, usize);
 
-impl<'s> List<'s> {
-    pub fn new(handle: Handle<'s>) -> Result<Self> {
-        if !handle.is_list() {
-            return Err(DartError::Api("expected a Dart List".into()));
+impl<'a> FromDartArg<'a> for f64 {
+    fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self> {
+        args.get_double_arg(index)
+    }
+}
+
+impl<'a> FromDartArg<'a> for f32 {
+    fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self> {
+        Ok(args.get_double_arg(index)? as f32)
+    }
+}
+
+impl<'a> FromDartArg<'a> for bool {
+    fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self> {
+        args.get_boolean_arg(index)
+    }
+}
+
+impl<'a> FromDartArg<'a> for String {
+    fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self> {
+        args.get_string_arg(index)?.to_string_lossy()
+    }
+}
+
+impl<'a> FromDartArg<'a> for Handle<'a> {
+    fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self> {
+        args.get_arg(index)
+    }
+}
+
+impl<'a> FromDartArg<'a> for SendPort {
+    fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self> {
+        SendPort::from_handle(args.get_arg(index)?)
+    }
+}
+
+impl<'a> FromDartArg<'a> for TypedDataView<'a> {
+    fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self> {
+        TypedDataView::acquire(args.get_arg(index)?)
+    }
+}
+
+/// Declares a newtype over [`Handle`] for one kind of Dart object, checked on conversion.
+macro_rules! typed_handle {
+    ($(#[$doc:meta])* $name:ident, $dart_type:literal, $is:ident) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy)]
+        pub struct $name<'s>(Handle<'s>);
+
+        impl<'s> $name<'s> {
+            pub fn handle(self) -> Handle<'s> {
+                self.0
+            }
         }
-        Ok(Self(handle))
+
+        impl<'s> TryFrom<Handle<'s>> for $name<'s> {
+            type Error = DartError;
+
+            fn try_from(handle: Handle<'s>) -> Result<Self> {
+                if handle.$is() {
+                    Ok(Self(handle))
+                } else {
+                    Err(DartError::type_mismatch($dart_type, handle))
+                }
+            }
+        }
+
+        impl<'s> From<$name<'s>> for Handle<'s> {
+            fn from(value: $name<'s>) -> Self {
+                value.0
+            }
+        }
+
+        impl<'s> IntoDart<'s> for $name<'s> {
+            fn into_dart(self, _scope: &Scope<'s>) -> Result<Handle<'s>> {
+                Ok(self.0)
+            }
+        }
+
+        impl<'a> FromDartArg<'a> for $name<'a> {
+            fn from_arg(args: &NativeArguments<'a>, index: i32) -> Result<Self> {
+                Self::try_from(args.get_arg(index)?)
+            }
+        }
+    };
+}
+
+typed_handle!(
+    /// A Dart `String`.
+    DartString,
+    "String",
+    is_string
+);
+
+impl<'s> DartString<'s> {
+    /// The length in UTF-16 code units, as Dart's `length` counts it.
+    pub fn len(self) -> Result<usize> {
+        let mut out = MaybeUninit::<isize>::uninit();
+        check(unsafe { sys::Dart_StringLength(self.0.raw, out.as_mut_ptr()) })?;
+        Ok(unsafe { out.assume_init() } as usize)
+    }
+
+    pub fn is_empty(self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// The length of the string's UTF-8 encoding in bytes.
+    pub fn utf8_len(self) -> Result<usize> {
+        let mut out = MaybeUninit::<isize>::uninit();
+        check(unsafe { sys::Dart_StringUTF8Length(self.0.raw, out.as_mut_ptr()) })?;
+        Ok(unsafe { out.assume_init() } as usize)
+    }
+
+    /// The string as UTF-8. The bytes live in the scope's zone, so this doesn't copy
+    /// them again.
+    pub fn as_str(self) -> Result<&'s str> {
+        let mut ptr_out = MaybeUninit::<*mut u8>::uninit();
+        let mut len_out = MaybeUninit::<isize>::uninit();
+        check(unsafe {
+            sys::Dart_StringToUTF8(self.0.raw, ptr_out.as_mut_ptr(), len_out.as_mut_ptr())
+        })?;
+        let bytes = unsafe {
+            std::slice::from_raw_parts(ptr_out.assume_init(), len_out.assume_init() as usize)
+        };
+        std::str::from_utf8(bytes).map_err(|error| DartError::Api(error.to_string()))
+    }
+}
+
+typed_handle!(
+    /// A Dart `int`.
+    DartInt,
+    "int",
+    is_integer
+);
+
+impl DartInt<'_> {
+    pub fn to_i64(self) -> Result<i64> {
+        self.0.to_i64()
+    }
+
+    pub fn to_u64(self) -> Result<u64> {
+        self.0.to_u64()
     }
+}
 
+typed_handle!(
+    /// A Dart `List`.
+    DartList,
+    "List",
+    is_list
+);
+
+impl<'s> DartList<'s> {
     pub fn len(&self) -> Result<isize> {
         let mut out = MaybeUninit::<isize>::uninit();
         check(unsafe { sys::Dart_ListLength(self.0.raw, out.as_mut_ptr() as *mut isize) })?;
         Ok(unsafe { out.assume_init() })
     }
 
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
     pub fn get(&self, scope: &Scope<'s>, index: isize) -> Result<Handle<'s>> {
         scope.check(unsafe { sys::Dart_ListGetAt(self.0.raw, index) })
     }
@@ -696,13 +2093,206 @@ impl<'s> List<'s> {
         check(unsafe { sys::Dart_ListSetAt(self.0.raw, index, value.raw) })?;
         Ok(())
     }
+
+    /// The elements in order.
+    pub fn iter<'a>(
+        &'a self,
+        scope: &'a Scope<'s>,
+    ) -> impl Iterator<Item = Result<Handle<'s>>> + 'a {
+        let len = self.len().unwrap_or(0);
+        (0..len).map(move |index| self.get(scope, index))
+    }
+}
+
+typed_handle!(
+    /// A Dart `Map`.
+    DartMap,
+    "Map",
+    is_map
+);
+
+impl<'s> DartMap<'s> {
+    pub fn keys(self, scope: &Scope<'s>) -> Result<DartList<'s>> {
+        self.0.map_keys(scope)
+    }
+
+    /// The value for `key`, or null if there is none.
+    pub fn get(self, scope: &Scope<'s>, key: Handle<'s>) -> Result<Handle<'s>> {
+        self.0.map_get(scope, key)
+    }
+
+    /// The entries in the map's iteration order.
+    pub fn iter<'a>(
+        self,
+        scope: &'a Scope<'s>,
+    ) -> Result<impl Iterator<Item = Result<(Handle<'s>, Handle<'s>)>> + 'a>
+    where
+        's: 'a,
+    {
+        let keys = self.keys(scope)?;
+        let len = keys.len()?;
+        Ok((0..len).map(move |index| {
+            let key = keys.get(scope, index)?;
+            Ok((key, self.get(scope, key)?))
+        }))
+    }
+}
+
+typed_handle!(
+    /// A Dart closure or tear-off.
+    DartClosure,
+    "Function",
+    is_closure
+);
+
+impl<'s> DartClosure<'s> {
+    /// Calls the closure with `args`, a tuple of values convertible with [`IntoDart`].
+    pub fn call(self, scope: &Scope<'s>, args: impl IntoDartArgs<'s>) -> Result<Handle<'s>> {
+        let mut args = args.into_dart_args(scope)?;
+        scope.check(unsafe {
+            sys::Dart_InvokeClosure(self.0.raw, args.len() as i32, args.as_mut_ptr())
+        })
+    }
 }
 
+typed_handle!(
+    /// Any Dart object other than null, with access to its fields and methods.
+    DartInstance,
+    "Object",
+    is_instance
+);
+
+impl<'s> DartInstance<'s> {
+    pub fn get_field(self, scope: &Scope<'s>, name: &str) -> Result<Handle<'s>> {
+        let name = scope.new_string(name)?;
+        scope.check(unsafe { sys::Dart_GetField(self.0.raw, name.raw) })
+    }
+
+    pub fn set_field(self, scope: &Scope<'s>, name: &str, value: impl IntoDart<'s>) -> Result<()> {
+        let name = scope.new_string(name)?;
+        let value = value.into_dart(scope)?;
+        check(unsafe { sys::Dart_SetField(self.0.raw, name.raw, value.raw) })?;
+        Ok(())
+    }
+
+    /// Calls the method `name` with `args`, a tuple of values convertible with
+    /// [`IntoDart`].
+    pub fn invoke(
+        self,
+        scope: &Scope<'s>,
+        name: &str,
+        args: impl IntoDartArgs<'s>,
+    ) -> Result<Handle<'s>> {
+        let name = scope.new_string(name)?;
+        let mut args = args.into_dart_args(scope)?;
+        scope.check(unsafe {
+            sys::Dart_Invoke(self.0.raw, name.raw, args.len() as i32, args.as_mut_ptr())
+        })
+    }
+}
+
+/// The arguments of a call from Rust into Dart: a tuple of [`IntoDart`] values.
+pub trait IntoDartArgs<'s> {
+    fn into_dart_args(self, scope: &Scope<'s>) -> Result<Vec<sys::Dart_Handle>>;
+}
+
+macro_rules! into_dart_args {
+    ($($arg:ident),*) => {
+        impl<'s, $($arg: IntoDart<'s>),*> IntoDartArgs<'s> for ($($arg,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            fn into_dart_args(self, scope: &Scope<'s>) -> Result<Vec<sys::Dart_Handle>> {
+                let ($($arg,)*) = self;
+                Ok(vec![$($arg.into_dart(scope)?.raw),*])
+            }
+        }
+    };
+}
+
+into_dart_args!();
+into_dart_args!(A);
+into_dart_args!(A, B);
+into_dart_args!(A, B, C);
+into_dart_args!(A, B, C, D);
+into_dart_args!(A, B, C, D, E);
+into_dart_args!(A, B, C, D, E, F);
+
+/// The kinds of Dart typed data, one per `Dart_TypedData_Type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypedDataKind {
+    ByteData,
+    Int8,
+    Uint8,
+    Uint8Clamped,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Int64,
+    Uint64,
+    Float32,
+    Float64,
+    Int32x4,
+    Float32x4,
+    Float64x2,
+}
+
+impl TypedDataKind {
+    pub fn raw(self) -> sys::Dart_TypedData_Type {
+        match self {
+            TypedDataKind::ByteData => sys::Dart_TypedData_Type_Dart_TypedData_kByteData,
+            TypedDataKind::Int8 => sys::Dart_TypedData_Type_Dart_TypedData_kInt8,
+            TypedDataKind::Uint8 => sys::Dart_TypedData_Type_Dart_TypedData_kUint8,
+            TypedDataKind::Uint8Clamped => sys::Dart_TypedData_Type_Dart_TypedData_kUint8Clamped,
+            TypedDataKind::Int16 => sys::Dart_TypedData_Type_Dart_TypedData_kInt16,
+            TypedDataKind::Uint16 => sys::Dart_TypedData_Type_Dart_TypedData_kUint16,
+            TypedDataKind::Int32 => sys::Dart_TypedData_Type_Dart_TypedData_kInt32,
+            TypedDataKind::Uint32 => sys::Dart_TypedData_Type_Dart_TypedData_kUint32,
+            TypedDataKind::Int64 => sys::Dart_TypedData_Type_Dart_TypedData_kInt64,
+            TypedDataKind::Uint64 => sys::Dart_TypedData_Type_Dart_TypedData_kUint64,
+            TypedDataKind::Float32 => sys::Dart_TypedData_Type_Dart_TypedData_kFloat32,
+            TypedDataKind::Float64 => sys::Dart_TypedData_Type_Dart_TypedData_kFloat64,
+            TypedDataKind::Int32x4 => sys::Dart_TypedData_Type_Dart_TypedData_kInt32x4,
+            TypedDataKind::Float32x4 => sys::Dart_TypedData_Type_Dart_TypedData_kFloat32x4,
+            TypedDataKind::Float64x2 => sys::Dart_TypedData_Type_Dart_TypedData_kFloat64x2,
+        }
+    }
+}
+
+/// Element types of the typed lists made by [`Scope::new_external_typed_data`].
+pub trait TypedDataElement: Copy {
+    const KIND: TypedDataKind;
+}
+
+macro_rules! typed_data_element {
+    ($($ty:ty => $kind:ident),* $(,)?) => {
+        $(
+            impl TypedDataElement for $ty {
+                const KIND: TypedDataKind = TypedDataKind::$kind;
+            }
+        )*
+    };
+}
+
+typed_data_element!(
+    i8 => Int8,
+    u8 => Uint8,
+    i16 => Int16,
+    u16 => Uint16,
+    i32 => Int32,
+    u32 => Uint32,
+    i64 => Int64,
+    u64 => Uint64,
+    f32 => Float32,
+    f64 => Float64,
+);
+
 /// A borrowed view over a Dart `TypedData` / `ByteData` buffer, released on drop.
 pub struct TypedDataView<'s> {
     object: Handle<'s>,
     pub ty: sys::Dart_TypedData_Type,
     pub data: *mut u8,
+    /// The number of elements, which are wider than a byte except in `Uint8List`s,
+    /// `Int8List`s and `ByteData`. See [`TypedDataView::byte_len`].
     pub len: isize,
 }
 
@@ -731,18 +2321,37 @@ impl<'s> TypedDataView<'s> {
         })
     }
 
+    /// The size of the data in bytes.
+    pub fn byte_len(&self) -> usize {
+        let element_size = match self.ty {
+            sys::Dart_TypedData_Type_Dart_TypedData_kInt16
+            | sys::Dart_TypedData_Type_Dart_TypedData_kUint16 => 2,
+            sys::Dart_TypedData_Type_Dart_TypedData_kInt32
+            | sys::Dart_TypedData_Type_Dart_TypedData_kUint32
+            | sys::Dart_TypedData_Type_Dart_TypedData_kFloat32 => 4,
+            sys::Dart_TypedData_Type_Dart_TypedData_kInt64
+            | sys::Dart_TypedData_Type_Dart_TypedData_kUint64
+            | sys::Dart_TypedData_Type_Dart_TypedData_kFloat64 => 8,
+            sys::Dart_TypedData_Type_Dart_TypedData_kInt32x4
+            | sys::Dart_TypedData_Type_Dart_TypedData_kFloat32x4
+            | sys::Dart_TypedData_Type_Dart_TypedData_kFloat64x2 => 16,
+            _ => 1,
+        };
+        self.len.max(0) as usize * element_size
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
-        if self.data.is_null() || self.len <= 0 {
+        if self.data.is_null() {
             return &[];
         }
-        unsafe { std::slice::from_raw_parts(self.data, self.len as usize) }
+        unsafe { std::slice::from_raw_parts(self.data, self.byte_len()) }
     }
 
     pub fn as_bytes_mut(&mut self) -> &mut [u8] {
-        if self.data.is_null() || self.len <= 0 {
+        if self.data.is_null() {
             return &mut [];
         }
-        unsafe { std::slice::from_raw_parts_mut(self.data, self.len as usize) }
+        unsafe { std::slice::from_raw_parts_mut(self.data, self.byte_len()) }
     }
 }
 
@@ -763,39 +2372,409 @@ pub fn null_handle<'s>(scope: &Scope<'s>) -> Handle<'s> {
     })
 }
 
-pub unsafe extern "C" fn native_resolver(
-    name: sys::Dart_Handle,
-    _num_of_arguments: ::std::os::raw::c_int,
-    _auto_setup_scope: *mut bool,
-) -> sys::Dart_NativeFunction {
-    let mut cstr = MaybeUninit::<*const i8>::uninit();
-    let res = sys::Dart_StringToCString(name, cstr.as_mut_ptr());
-    debug_assert!(!res.is_null(), "Dart_StringToCString returned null");
-    let name = CStr::from_ptr(cstr.assume_init());
-    for function in inventory::iter::<NativeFunction>() {
-        if function.name == name.to_str().unwrap() {
-            return Some(function.function);
+/// The `Dart_NativeEntryResolver` to install on the libraries of `namespace`, resolving
+/// only the natives registered in it. `None` if no [`NativeLibrary`] declares the
+/// namespace.
+pub fn native_resolver(namespace: &str) -> sys::Dart_NativeEntryResolver {
+    library_namespaces()
+        .iter()
+        .position(|&library_namespace| library_namespace == namespace)
+        .map(|index| RESOLVERS[index])
+}
+
+type Resolver = unsafe extern "C" fn(
+    sys::Dart_Handle,
+    ::std::os::raw::c_int,
+    *mut bool,
+) -> sys::Dart_NativeFunction;
+
+/// The VM passes resolvers no user data, so each namespace gets its own instantiation,
+/// picked by the namespace's index in [`library_namespaces`].
+const RESOLVERS: [Resolver; 8] = [
+    resolve_in::<0>,
+    resolve_in::<1>,
+    resolve_in::<2>,
+    resolve_in::<3>,
+    resolve_in::<4>,
+    resolve_in::<5>,
+    resolve_in::<6>,
+    resolve_in::<7>,
+];
+
+/// The namespaces of the registered native libraries, in a fixed order.
+fn library_namespaces() -> &'static [&'static str] {
+    static NAMESPACES: OnceLock<Vec<&'static str>> = OnceLock::new();
+    NAMESPACES.get_or_init(|| {
+        let mut namespaces: Vec<_> = inventory::iter::<NativeLibrary>()
+            .map(NativeLibrary::namespace)
+            .collect();
+        namespaces.sort_unstable();
+        namespaces.dedup();
+        assert!(
+            namespaces.len() <= RESOLVERS.len(),
+            "native libraries use {} namespaces, at most {} are supported",
+            namespaces.len(),
+            RESOLVERS.len()
+        );
+        namespaces
+    })
+}
+
+/// # Safety
+///
+/// Called by the VM with the current isolate entered and `name` a Dart string.
+unsafe extern "C" fn resolve_in<const N: usize>(
+    name: sys::Dart_Handle,
+    num_of_arguments: ::std::os::raw::c_int,
+    _auto_setup_scope: *mut bool,
+) -> sys::Dart_NativeFunction {
+    let namespace = library_namespaces()[N];
+    let mut cstr = MaybeUninit::<*const i8>::uninit();
+    let res = sys::Dart_StringToCString(name, cstr.as_mut_ptr());
+    debug_assert!(!res.is_null(), "Dart_StringToCString returned null");
+    let name = CStr::from_ptr(cstr.assume_init()).to_string_lossy();
+    let resolved = match IsolateData::current() {
+        Some(data) if !data.allows(namespace) => Err(ResolveError::Namespace {
+            name: name.to_string(),
+            namespace,
+        }),
+        _ => resolve(namespace, &name, num_of_arguments as usize),
+    };
+    match resolved {
+        Ok(function) => Some(function.function),
+        Err(error) => {
+            let collected = RESOLVE_FAILURES.with_borrow_mut(|failures| {
+                failures
+                    .as_mut()
+                    .map(|failures| failures.push(error.to_string()))
+            });
+            if collected.is_some() {
+                // Keep compiling so every failure is reported.
+                return Some(unresolved);
+            }
+            // The VM only reports that the native can't be found, so say why.
+            eprintln!("native resolution failed: {}", error);
+            None
+        }
+    }
+}
+
+thread_local! {
+    /// Resolution failures collected by [`Scope::check_natives`], which is running while
+    /// this is `Some`.
+    static RESOLVE_FAILURES: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Stands in for the natives [`Scope::check_natives`] failed to resolve.
+unsafe extern "C" fn unresolved(args: sys::Dart_NativeArguments) {
+    let error = sys::Dart_NewApiError(c"native function could not be resolved".as_ptr());
+    sys::Dart_SetReturnValue(args, error);
+}
+
+/// Looks up the native registered as `name` in `namespace` and checks that Dart calls it
+/// with the number of arguments (receiver included) its shim extracts.
+pub fn resolve(
+    namespace: &str,
+    name: &str,
+    num_of_arguments: usize,
+) -> std::result::Result<&'static NativeFunction, ResolveError> {
+    let registry: &HashMap<(&str, &str), &NativeFunction> = registry();
+    let Some(&function) = registry.get(&(namespace, name)) else {
+        // Resolution misses are rare, so a scan to explain them is fine.
+        return Err(
+            match inventory::iter::<NativeFunction>().find(|function| function.name == name) {
+                Some(function) => ResolveError::OtherNamespace {
+                    name: function.name,
+                    namespace: namespace.to_string(),
+                    registered: function.namespace,
+                },
+                None => ResolveError::Unregistered {
+                    name: name.to_string(),
+                    namespace: namespace.to_string(),
+                },
+            },
+        );
+    };
+    match function.arity {
+        Some(expected) if expected != num_of_arguments => Err(ResolveError::Arity {
+            name: function.name,
+            expected,
+            found: num_of_arguments,
+        }),
+        _ => Ok(function),
+    }
+}
+
+/// The registered natives by namespace and name, indexed once on first lookup so
+/// resolution doesn't scan the whole registry.
+///
+/// # Panics
+///
+/// If a name is registered twice in the same namespace.
+fn registry() -> &'static HashMap<(&'static str, &'static str), &'static NativeFunction> {
+    static REGISTRY: OnceLock<HashMap<(&str, &str), &NativeFunction>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = HashMap::new();
+        for function in inventory::iter::<NativeFunction>() {
+            let key = (function.namespace, function.name);
+            if registry.insert(key, function).is_some() {
+                panic!(
+                    "native `{}` is registered twice in namespace `{}`",
+                    function.name, function.namespace
+                );
+            }
+        }
+        registry
+    })
+}
+
+/// The namespace natives are registered under unless their macro names another.
+pub const DEFAULT_NAMESPACE: &str = "app";
+
+/// A Dart library whose `external` members resolve against the native registry, and
+/// the namespace of the natives it declares. Register more with `inventory::submit!`.
+pub struct NativeLibrary {
+    uri: &'static str,
+    namespace: &'static str,
+}
+
+impl NativeLibrary {
+    pub const fn new(uri: &'static str, namespace: &'static str) -> Self {
+        Self { uri, namespace }
+    }
+
+    pub fn uri(&self) -> &'static str {
+        self.uri
+    }
+
+    pub fn namespace(&self) -> &'static str {
+        self.namespace
+    }
+}
+
+inventory::collect!(NativeLibrary);
+
+inventory::submit! {
+    NativeLibrary::new("package:app/native.dart", DEFAULT_NAMESPACE)
+}
+
+/// Drops the current isolate's cached class handles.
+pub(crate) fn clear_class_cache() {
+    if let Some(data) = IsolateData::current() {
+        data.classes.borrow_mut().clear();
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResolveError {
+    #[error("`{name}` is not a registered native in namespace `{namespace}`")]
+    Unregistered { name: String, namespace: String },
+    #[error("`{name}` takes {expected} arguments but Dart passes {found}")]
+    Arity {
+        name: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    #[error("`{name}` is registered in namespace `{registered}`, not `{namespace}`")]
+    OtherNamespace {
+        name: &'static str,
+        namespace: String,
+        registered: &'static str,
+    },
+    #[error("`{name}` is in namespace `{namespace}`, which this isolate can't resolve")]
+    Namespace {
+        name: String,
+        namespace: &'static str,
+    },
+}
+
+pub struct NativeFunction {
+    name: &'static str,
+    function: unsafe extern "C" fn(args: sys::Dart_NativeArguments),
+    namespace: &'static str,
+    arity: Option<usize>,
+    signature: Option<NativeSignature>,
+}
+
+impl NativeFunction {
+    pub const fn new(
+        name: &'static str,
+        function: unsafe extern "C" fn(args: sys::Dart_NativeArguments),
+    ) -> Self {
+        Self {
+            name,
+            function,
+            namespace: DEFAULT_NAMESPACE,
+            arity: None,
+            signature: None,
+        }
+    }
+
+    pub const fn in_namespace(mut self, namespace: &'static str) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// The number of Dart arguments, receiver included, the shim reads. Natives without
+    /// one (raw `NativeArguments` without a signature) are resolved at any arity.
+    pub const fn with_arity(mut self, arity: usize) -> Self {
+        self.arity = Some(arity);
+        self
+    }
+
+    pub const fn with_signature(mut self, signature: NativeSignature) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn namespace(&self) -> &'static str {
+        self.namespace
+    }
+
+    pub fn arity(&self) -> Option<usize> {
+        self.arity
+    }
+
+    /// The Dart declaration emitted by the native macros, if the signature could be
+    /// derived or was given with `#[dart(signature = "...")]`.
+    pub fn signature(&self) -> Option<&NativeSignature> {
+        self.signature.as_ref()
+    }
+}
+
+/// Dart-side shape of a native function, used to generate the `.g.dart` bindings.
+pub struct NativeSignature {
+    /// Class the external is declared on, or `None` for a top-level function.
+    pub class: Option<&'static str>,
+    pub dart_name: &'static str,
+    /// The Rust doc comment, one line per `///`.
+    pub docs: &'static str,
+    pub return_type: &'static str,
+    pub params: &'static [NativeParam],
+}
+
+pub struct NativeParam {
+    /// Type and name, e.g. `int index`.
+    pub declaration: &'static str,
+    /// Optional positional parameter, emitted inside `[...]`.
+    pub optional: bool,
+}
+
+/// A Rust type that Dart sees as a `NativeFieldWrapperClass1` instance carrying it as
+/// its peer. Derive it with `#[derive(NativePeer)]`; the Dart class shares the type's name.
+///
+/// Peers are `Send` because the VM may finalize their objects, dropping them, on any
+/// thread.
+pub trait NativePeer: Sized + Send + 'static {
+    const CLASS_NAME: &'static str;
+    const LIBRARY: &'static str = "package:app/native.dart";
+
+    /// The native memory the peer holds, reported to the Dart GC.
+    fn external_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+    }
+}
+
+/// Converts the return value of a typed native into the Dart object handed back.
+pub trait IntoDart<'s> {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>>;
+}
+
+impl<'s> IntoDart<'s> for () {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        scope.null_handle()
+    }
+}
+
+impl<'s> IntoDart<'s> for bool {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        scope.new_boolean(self)
+    }
+}
+
+macro_rules! impl_into_dart_int {
+    ($($ty:ty),*) => {
+        $(
+            impl<'s> IntoDart<'s> for $ty {
+                fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+                    let value = i64::try_from(self).map_err(|_| {
+                        DartError::Api(format!("{} does not fit in a Dart int", self))
+                    })?;
+                    scope.new_integer(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_into_dart_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl<'s> IntoDart<'s> for f32 {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        scope.new_double(self as f64)
+    }
+}
+
+impl<'s> IntoDart<'s> for f64 {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        scope.new_double(self)
+    }
+}
+
+impl<'s> IntoDart<'s> for String {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        scope.new_string(&self)
+    }
+}
+
+impl<'s> IntoDart<'s> for Handle<'s> {
+    fn into_dart(self, _scope: &Scope<'s>) -> Result<Handle<'s>> {
+        Ok(self)
+    }
+}
+
+impl<'s, T: IntoDart<'s>> IntoDart<'s> for Option<T> {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        match self {
+            Some(value) => value.into_dart(scope),
+            None => scope.null_handle(),
         }
     }
+}
 
-    None
+impl<'s, T: IntoDart<'s>, E: Into<DartError>> IntoDart<'s> for std::result::Result<T, E> {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        self.map_err(Into::into)?.into_dart(scope)
+    }
 }
 
-pub struct NativeFunction {
-    name: &'static str,
-    function: unsafe extern "C" fn(args: sys::Dart_NativeArguments),
+impl<'s, T: NativePeer> IntoDart<'s> for T {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        let class = scope.class(T::LIBRARY, T::CLASS_NAME)?;
+        let instance = scope.new_object(class, scope.null_handle()?, &mut [])?;
+        let external_size = self.external_size();
+        instance.set_peer_with_size(Box::new(self), external_size)?;
+        Ok(instance)
+    }
 }
 
-impl NativeFunction {
-    pub const fn new(
-        name: &'static str,
-        function: unsafe extern "C" fn(args: sys::Dart_NativeArguments),
-    ) -> Self {
-        Self { name, function }
+/// Returns a serializable value from a typed native through [`to_dart`].
+pub struct Serde<T>(pub T);
+
+impl<'s, T: Serialize> IntoDart<'s> for Serde<T> {
+    fn into_dart(self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        to_dart(scope, &self.0)
     }
 }
 
-use serde::de::{self, DeserializeOwned, MapAccess, SeqAccess, Visitor};
+use serde::de::{
+    self, DeserializeOwned, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
+    Visitor,
+};
 
 pub fn from_dart<T: DeserializeOwned>(handle: Handle) -> Result<T> {
     let scope = Isolate::current()?;
@@ -806,6 +2785,28 @@ pub fn from_dart<T: DeserializeOwned>(handle: Handle) -> Result<T> {
     T::deserialize(&mut deserializer)
 }
 
+/// Deserializes the native argument at `index`.
+///
+/// Dart objects that are not plain data (maps, lists, strings, numbers, typed data)
+/// are converted through their `toMap()` method first, which is how descriptor classes
+/// in `gpu.dart` expose their fields.
+pub fn from_dart_arg<T: DeserializeOwned>(args: &NativeArguments, index: i32) -> Result<T> {
+    let handle = args.get_arg(index)?;
+    let is_plain_data = handle.is_null()
+        || handle.is_boolean()
+        || handle.is_integer()
+        || handle.is_double()
+        || handle.is_string()
+        || handle.is_list()
+        || handle.is_map()
+        || handle.is_typed_data();
+    if is_plain_data {
+        return from_dart(handle);
+    }
+    let scope = Isolate::current()?;
+    from_dart(handle.invoke(scope.new_string("toMap")?, &mut [])?)
+}
+
 impl de::Error for DartError {
     fn custom<T: std::fmt::Display>(msg: T) -> Self {
         DartError::Api(msg.to_string())
@@ -838,6 +2839,8 @@ impl<'a, 's, 'de> de::Deserializer<'de> for &'a mut Deserializer<'a, 's> {
             self.deserialize_seq(visitor)
         } else if self.handle.is_map() {
             self.deserialize_map(visitor)
+        } else if self.handle.is_typed_data() {
+            self.deserialize_byte_buf(visitor)
         } else {
             Err(DartError::Api("Unsupported Dart type".into()))
         }
@@ -946,18 +2949,38 @@ impl<'a, 's, 'de> de::Deserializer<'de> for &'a mut Deserializer<'a, 's> {
         visitor.visit_string(self.handle.to_string_lossy()?)
     }
 
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(DartError::Api("bytes not supported".into()))
+        if self.handle.is_typed_data() {
+            let view = TypedDataView::acquire(self.handle)?;
+            visitor.visit_bytes(view.as_bytes())
+        } else {
+            self.deserialize_byte_buf(visitor)
+        }
     }
 
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(DartError::Api("byte_buf not supported".into()))
+        if self.handle.is_typed_data() {
+            let view = TypedDataView::acquire(self.handle)?;
+            return visitor.visit_byte_buf(view.as_bytes().to_vec());
+        }
+
+        // A plain `List<int>` is accepted too, one element at a time.
+        let list = DartList::try_from(self.handle)?;
+        let len = list.len()?;
+        let mut bytes = Vec::with_capacity(len as usize);
+        for index in 0..len {
+            let value = list.get(self.scope, index)?.to_i64()?;
+            let byte = u8::try_from(value)
+                .map_err(|_| DartError::Api(format!("{value} at index {index} is not a byte")))?;
+            bytes.push(byte);
+        }
+        visitor.visit_byte_buf(bytes)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
@@ -996,7 +3019,7 @@ impl<'a, 's, 'de> de::Deserializer<'de> for &'a mut Deserializer<'a, 's> {
     where
         V: Visitor<'de>,
     {
-        let list = List::new(self.handle)?;
+        let list = DartList::try_from(self.handle)?;
         let len = list.len()? as usize;
         visitor.visit_seq(SeqAccessImpl {
             list,
@@ -1054,14 +3077,37 @@ impl<'a, 's, 'de> de::Deserializer<'de> for &'a mut Deserializer<'a, 's> {
 
     fn deserialize_enum<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variants: &'static [&'static str],
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(DartError::Api("enum not supported".into()))
+        // Externally tagged: a string names a unit variant, a single-entry map
+        // `{variant: payload}` carries a data variant. Internally and adjacently
+        // tagged enums never get here, serde routes them through `deserialize_any`.
+        if self.handle.is_string() {
+            visitor.visit_enum(self.handle.to_string_lossy()?.into_deserializer())
+        } else if self.handle.is_map() {
+            let keys = self.handle.map_keys(self.scope)?;
+            if keys.len()? != 1 {
+                return Err(DartError::Api(format!(
+                    "expected a single-entry map for enum {name}"
+                )));
+            }
+            let variant = keys.get(self.scope, 0)?;
+            let value = self.handle.map_get(self.scope, variant)?;
+            visitor.visit_enum(EnumAccessImpl {
+                variant,
+                value,
+                scope: self.scope,
+            })
+        } else {
+            Err(DartError::Api(format!(
+                "expected a string or map for enum {name}"
+            )))
+        }
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
@@ -1080,7 +3126,7 @@ impl<'a, 's, 'de> de::Deserializer<'de> for &'a mut Deserializer<'a, 's> {
 }
 
 struct SeqAccessImpl<'a, 's> {
-    list: List<'s>,
+    list: DartList<'s>,
     scope: &'a Scope<'s>,
     index: usize,
     len: usize,
@@ -1109,7 +3155,7 @@ impl<'a, 's, 'de> SeqAccess<'de> for SeqAccessImpl<'a, 's> {
 
 struct MapAccessImpl<'a, 's> {
     map: Handle<'s>,
-    keys: List<'s>,
+    keys: DartList<'s>,
     scope: &'a Scope<'s>,
     index: usize,
     len: usize,
@@ -1149,4 +3195,454 @@ impl<'a, 's, 'de> MapAccess<'de> for MapAccessImpl<'a, 's> {
     }
 }
 
+struct EnumAccessImpl<'a, 's> {
+    variant: Handle<'s>,
+    value: Handle<'s>,
+    scope: &'a Scope<'s>,
+}
+
+impl<'a, 's, 'de> EnumAccess<'de> for EnumAccessImpl<'a, 's> {
+    type Error = DartError;
+    type Variant = VariantAccessImpl<'a, 's>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let mut deserializer = Deserializer {
+            handle: self.variant,
+            scope: self.scope,
+        };
+        let variant = seed.deserialize(&mut deserializer)?;
+        Ok((
+            variant,
+            VariantAccessImpl {
+                value: self.value,
+                scope: self.scope,
+            },
+        ))
+    }
+}
+
+struct VariantAccessImpl<'a, 's> {
+    value: Handle<'s>,
+    scope: &'a Scope<'s>,
+}
+
+impl<'a, 's, 'de> VariantAccess<'de> for VariantAccessImpl<'a, 's> {
+    type Error = DartError;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let mut deserializer = Deserializer {
+            handle: self.value,
+            scope: self.scope,
+        };
+        seed.deserialize(&mut deserializer)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut deserializer = Deserializer {
+            handle: self.value,
+            scope: self.scope,
+        };
+        de::Deserializer::deserialize_seq(&mut deserializer, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut deserializer = Deserializer {
+            handle: self.value,
+            scope: self.scope,
+        };
+        de::Deserializer::deserialize_map(&mut deserializer, visitor)
+    }
+}
+
+use serde::ser::{self, Serialize};
+
+/// Converts a Rust value into a Dart object.
+///
+/// Structs and maps become `Map`s (`Map<String, dynamic>` when every key is a string),
+/// sequences and tuples become `List<dynamic>`, byte buffers become `Uint8List` and
+/// `None`/unit become `null`. Enums use the externally tagged representation: unit
+/// variants are strings, data variants are single-entry maps.
+pub fn to_dart<'s, T: Serialize + ?Sized>(scope: &Scope<'s>, value: &T) -> Result<Handle<'s>> {
+    value.serialize(Serializer { scope })
+}
+
+impl ser::Error for DartError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        DartError::Api(msg.to_string())
+    }
+}
+
+struct Serializer<'a, 's> {
+    scope: &'a Scope<'s>,
+}
+
+impl<'a, 's> ser::Serializer for Serializer<'a, 's> {
+    type Ok = Handle<'s>;
+    type Error = DartError;
+
+    type SerializeSeq = SeqSerializer<'a, 's>;
+    type SerializeTuple = SeqSerializer<'a, 's>;
+    type SerializeTupleStruct = SeqSerializer<'a, 's>;
+    type SerializeTupleVariant = VariantSerializer<'a, 's, SeqSerializer<'a, 's>>;
+    type SerializeMap = MapSerializer<'a, 's>;
+    type SerializeStruct = MapSerializer<'a, 's>;
+    type SerializeStructVariant = VariantSerializer<'a, 's, MapSerializer<'a, 's>>;
+
+    fn serialize_bool(self, v: bool) -> Result<Handle<'s>> {
+        self.scope.new_boolean(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Handle<'s>> {
+        self.scope.new_integer(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Handle<'s>> {
+        self.scope.new_integer(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Handle<'s>> {
+        self.scope.new_integer(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Handle<'s>> {
+        self.scope.new_integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Handle<'s>> {
+        self.scope.new_integer(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Handle<'s>> {
+        self.scope.new_integer(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Handle<'s>> {
+        self.scope.new_integer(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Handle<'s>> {
+        self.scope
+            .check(unsafe { sys::Dart_NewIntegerFromUint64(v) })
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Handle<'s>> {
+        self.scope.new_double(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Handle<'s>> {
+        self.scope.new_double(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Handle<'s>> {
+        self.scope.new_string(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Handle<'s>> {
+        self.scope.new_string(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Handle<'s>> {
+        let list = self.scope.new_typed_data(TypedDataKind::Uint8, v.len())?;
+        let mut view = TypedDataView::acquire(list)?;
+        view.as_bytes_mut().copy_from_slice(v);
+        drop(view);
+        Ok(list)
+    }
+
+    fn serialize_none(self) -> Result<Handle<'s>> {
+        self.scope.null_handle()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Handle<'s>> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Handle<'s>> {
+        self.scope.null_handle()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Handle<'s>> {
+        self.scope.null_handle()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Handle<'s>> {
+        self.scope.new_string(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Handle<'s>> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Handle<'s>> {
+        let value = to_dart(self.scope, value)?;
+        single_entry_map(self.scope, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer<'a, 's>> {
+        Ok(SeqSerializer {
+            scope: self.scope,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a, 's>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a, 's>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(VariantSerializer {
+            scope: self.scope,
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer<'a, 's>> {
+        Ok(MapSerializer {
+            scope: self.scope,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            pending_key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer<'a, 's>> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(VariantSerializer {
+            scope: self.scope,
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SeqSerializer<'a, 's> {
+    scope: &'a Scope<'s>,
+    items: Vec<Handle<'s>>,
+}
+
+impl<'a, 's> SeqSerializer<'a, 's> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.push(to_dart(self.scope, value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Handle<'s>> {
+        let list = DartList::try_from(self.scope.new_list(self.items.len())?)?;
+        for (index, item) in self.items.into_iter().enumerate() {
+            list.set(index as isize, item)?;
+        }
+        Ok(list.0)
+    }
+}
+
+impl<'s> ser::SerializeSeq for SeqSerializer<'_, 's> {
+    type Ok = Handle<'s>;
+    type Error = DartError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Handle<'s>> {
+        self.finish()
+    }
+}
+
+impl<'s> ser::SerializeTuple for SeqSerializer<'_, 's> {
+    type Ok = Handle<'s>;
+    type Error = DartError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Handle<'s>> {
+        self.finish()
+    }
+}
+
+impl<'s> ser::SerializeTupleStruct for SeqSerializer<'_, 's> {
+    type Ok = Handle<'s>;
+    type Error = DartError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Handle<'s>> {
+        self.finish()
+    }
+}
+
+struct MapSerializer<'a, 's> {
+    scope: &'a Scope<'s>,
+    entries: Vec<(Handle<'s>, Handle<'s>)>,
+    pending_key: Option<Handle<'s>>,
+}
+
+impl<'a, 's> MapSerializer<'a, 's> {
+    fn finish(self) -> Result<Handle<'s>> {
+        let string_keys = self.entries.iter().all(|(key, _)| key.is_string());
+        let map = new_map(self.scope, string_keys)?;
+        let index_set = self.scope.new_string("[]=")?;
+        for (key, value) in self.entries {
+            map.invoke(index_set, &mut [key.raw, value.raw])?;
+        }
+        Ok(map)
+    }
+}
+
+impl<'s> ser::SerializeMap for MapSerializer<'_, 's> {
+    type Ok = Handle<'s>;
+    type Error = DartError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.pending_key = Some(to_dart(self.scope, key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .pending_key
+            .take()
+            .ok_or_else(|| DartError::Api("serialize_value called before serialize_key".into()))?;
+        self.entries.push((key, to_dart(self.scope, value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Handle<'s>> {
+        self.finish()
+    }
+}
+
+impl<'s> ser::SerializeStruct for MapSerializer<'_, 's> {
+    type Ok = Handle<'s>;
+    type Error = DartError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let key = self.scope.new_string(key)?;
+        self.entries.push((key, to_dart(self.scope, value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Handle<'s>> {
+        self.finish()
+    }
+}
+
+/// Wraps the payload of a tuple/struct variant as `{variant: payload}`.
+struct VariantSerializer<'a, 's, S> {
+    scope: &'a Scope<'s>,
+    variant: &'static str,
+    inner: S,
+}
+
+impl<'s> ser::SerializeTupleVariant for VariantSerializer<'_, 's, SeqSerializer<'_, 's>> {
+    type Ok = Handle<'s>;
+    type Error = DartError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.inner.push(value)
+    }
+
+    fn end(self) -> Result<Handle<'s>> {
+        let payload = self.inner.finish()?;
+        single_entry_map(self.scope, self.variant, payload)
+    }
+}
+
+impl<'s> ser::SerializeStructVariant for VariantSerializer<'_, 's, MapSerializer<'_, 's>> {
+    type Ok = Handle<'s>;
+    type Error = DartError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Handle<'s>> {
+        let payload = self.inner.finish()?;
+        single_entry_map(self.scope, self.variant, payload)
+    }
+}
+
+/// Creates an empty `Map<String, dynamic>` (or `Map<dynamic, dynamic>`).
+fn new_map<'s>(scope: &Scope<'s>, string_keys: bool) -> Result<Handle<'s>> {
+    let dynamic = scope.dynamic_type()?;
+    let key_type = if string_keys {
+        scope.non_nullable_type("dart:core", "String", &[])?
+    } else {
+        dynamic
+    };
+    scope.new_map(key_type, dynamic)
+}
+
+fn single_entry_map<'s>(scope: &Scope<'s>, key: &str, value: Handle<'s>) -> Result<Handle<'s>> {
+    let map = new_map(scope, true)?;
+    let index_set = scope.new_string("[]=")?;
+    map.invoke(index_set, &mut [scope.new_string(key)?.raw, value.raw])?;
+    Ok(map)
+}
+
 inventory::collect!(NativeFunction);
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+
+use crate::dart_api::{Isolate, IsolateData, Runtime, RuntimeConfig};
+use crate::hot_reload::HotReloader;
+use crate::service::{ServiceExtension, ServiceParams, ServiceResult};
+use crate::{registry, service, snapshot, window};
+
+/// How long the event loop may run between two steps of the windows.
+const EVENT_LOOP_BUDGET: Duration = Duration::from_millis(4);
+
+/// How long an app without windows sleeps waiting for a message before the frame loop
+/// checks for Ctrl+C and restart requests again.
+const IDLE_WAIT: Duration = Duration::from_millis(50);
+
+/// Set by the Ctrl+C handler; the frame loop stops at the next step.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set to have the frame loop hot-restart the app at the next step.
+static RESTART_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Asks the running engine to hot-restart the app: load the script into a new isolate and
+/// run the entry point again, carrying over what the app retained in the [`registry`].
+pub(crate) fn request_restart() {
+    RESTART_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+fn hot_restart(_params: &ServiceParams) -> ServiceResult {
+    request_restart();
+    Ok(serde_json::json!({}))
+}
+
+inventory::submit! {
+    ServiceExtension::root("ext.bigfish.hotRestart", hot_restart)
+}
+
+/// Builds an [`Engine`]. The defaults run `app/lib/main.dart` from the working directory.
+pub struct EngineBuilder {
+    service_port: u16,
+    hmr: bool,
+    hot_restart: bool,
+    check_natives: bool,
+    script: String,
+    snapshot: bool,
+    package_config: String,
+    entry_library: Option<String>,
+    entry_point: String,
+    args: Vec<String>,
+    environment: HashMap<String, String>,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self {
+            service_port: 5858,
+            hmr: false,
+            hot_restart: true,
+            check_natives: false,
+            script: "./app/lib/main.dart".into(),
+            snapshot: false,
+            package_config: "./app/.dart_tool/package_config.json".into(),
+            entry_library: None,
+            entry_point: "main".into(),
+            args: Vec::new(),
+            environment: HashMap::new(),
+        }
+    }
+}
+
+impl EngineBuilder {
+    /// Port of the VM service, which hot reload connects to.
+    pub fn service_port(mut self, service_port: u16) -> Self {
+        self.service_port = service_port;
+        self
+    }
+
+    /// Starts the VM service and reloads the app when files in the script's directory
+    /// change.
+    pub fn hmr(mut self, hmr: bool) -> Self {
+        self.hmr = hmr;
+        self
+    }
+
+    /// With hot reload on, restart the app when a reload is rejected, e.g. because a
+    /// class changed shape. On by default.
+    pub fn hot_restart(mut self, hot_restart: bool) -> Self {
+        self.hot_restart = hot_restart;
+        self
+    }
+
+    /// Resolve every native declared in the loaded native libraries before running
+    /// `main`, and fail listing any that are unregistered or take the wrong number of
+    /// arguments. Not available for snapshots.
+    pub fn check_natives(mut self, check_natives: bool) -> Self {
+        self.check_natives = check_natives;
+        self
+    }
+
+    pub fn script(mut self, script: impl Into<String>) -> Self {
+        self.script = script.into();
+        self.snapshot = false;
+        self
+    }
+
+    /// Starts from a kernel snapshot built by `bigfish snapshot` instead of a script.
+    pub fn snapshot(mut self, snapshot: impl Into<String>) -> Self {
+        self.script = snapshot.into();
+        self.snapshot = true;
+        self
+    }
+
+    pub fn package_config(mut self, package_config: impl Into<String>) -> Self {
+        self.package_config = package_config.into();
+        self
+    }
+
+    /// The library declaring the entry point, by default the script's own.
+    pub fn entry_library(mut self, entry_library: impl Into<String>) -> Self {
+        self.entry_library = Some(entry_library.into());
+        self
+    }
+
+    /// The function that is run, `main` by default.
+    pub fn entry_point(mut self, entry_point: impl Into<String>) -> Self {
+        self.entry_point = entry_point.into();
+        self
+    }
+
+    /// Arguments for an entry point taking a `List<String>`.
+    pub fn args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Defines `name` for `String.fromEnvironment` and friends.
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.environment.insert(name.into(), value.into());
+        self
+    }
+
+    /// Starts the VM and loads the script into the main isolate.
+    pub fn build(self) -> anyhow::Result<Engine> {
+        if self.snapshot {
+            snapshot::check(Path::new(&self.script))?;
+            anyhow::ensure!(
+                !self.hmr,
+                "hot reload needs the app's sources, not a snapshot"
+            );
+            anyhow::ensure!(
+                !self.check_natives,
+                "natives are checked against the app's sources, not a snapshot"
+            );
+        }
+        install_interrupt_handler();
+
+        let runtime = Runtime::initialize(RuntimeConfig::new(self.service_port, self.hmr))?;
+        let script = CString::new(self.script.as_str())?;
+        let package_config = CString::new(self.package_config)?;
+        let isolate = runtime.load_script(
+            &script,
+            &package_config,
+            IsolateData::new(()).with_environment(self.environment.clone()),
+        )?;
+        let mut engine = Engine {
+            isolate,
+            watcher: None,
+            script,
+            package_config,
+            environment: self.environment,
+            check_natives: self.check_natives,
+            entry_library: self.entry_library,
+            entry_point: self.entry_point,
+            args: self.args,
+            runtime,
+        };
+
+        if self.hmr {
+            let sources = Path::new(&self.script)
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            let watcher = HotReloader::spawn(sources.to_path_buf(), self.hot_restart)
+                .context("failed to start hot-reload watcher")?;
+            engine.watcher = Some(watcher);
+        }
+        Ok(engine)
+    }
+}
+
+/// The Dart VM with the app's main isolate and everything the app opens from it.
+///
+/// Dropping the engine (on return from [`Engine::run`], an error or a panic) shuts it down
+/// in order: the windows, the isolate, which finalizes the peers still referenced from
+/// Dart, the hot-reload watcher, and finally the VM.
+pub struct Engine {
+    isolate: Isolate,
+    watcher: Option<HotReloader>,
+    script: CString,
+    package_config: CString,
+    environment: HashMap<String, String>,
+    check_natives: bool,
+    entry_library: Option<String>,
+    entry_point: String,
+    args: Vec<String>,
+    // Declared last so the VM outlives everything above.
+    runtime: Runtime,
+}
+
+impl Engine {
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
+    /// Starts the entry point, then runs the frame loop until the last window closes, or,
+    /// for an app that never opened one, until nothing is left to wait for. Ctrl+C stops
+    /// the loop at the next step.
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        if self.check_natives {
+            self.validate_natives()?;
+        }
+        self.start()?;
+        while self.run_frames() {
+            self.restart()?;
+        }
+        Ok(())
+    }
+
+    fn validate_natives(&mut self) -> anyhow::Result<()> {
+        let scope = self.isolate.enter();
+        let failures = scope.check_natives()?;
+        for failure in &failures {
+            eprintln!("{}", failure);
+        }
+        anyhow::ensure!(
+            failures.is_empty(),
+            "{} natives failed to resolve",
+            failures.len()
+        );
+        Ok(())
+    }
+
+    /// Installs the engine into the isolate and schedules the entry point, which runs on
+    /// the first turn of the event loop.
+    fn start(&mut self) -> anyhow::Result<()> {
+        let mut scope = self.isolate.enter();
+        scope.install_native_resolvers();
+        service::register_extensions();
+        let library = match &self.entry_library {
+            Some(library) => scope.library(library)?,
+            None => scope.root_library()?,
+        };
+        scope.start_entry_point(library, &self.entry_point, &self.args)?;
+        Ok(())
+    }
+
+    /// Runs the frame loop. Returns true when it stopped for a hot restart.
+    fn run_frames(&mut self) -> bool {
+        let mut scope = self.isolate.enter();
+        // The entry point sets up windows and callbacks and returns; from here the engine
+        // owns the loop.
+        let mut windowed = false;
+        while !INTERRUPTED.load(Ordering::Relaxed) {
+            if RESTART_REQUESTED.swap(false, Ordering::Relaxed) {
+                return true;
+            }
+            let running = if window::has_windows() {
+                windowed = true;
+                window::poll_windows(&mut scope).unwrap_or_else(|error| {
+                    eprintln!("Error polling windows: {}", error.pretty());
+                    true
+                })
+            } else {
+                !windowed && scope.has_live_ports()
+            };
+            if !running {
+                break;
+            }
+            if !windowed {
+                // Nothing to draw, so sleep until a timer or port has a message.
+                scope.wait_for_messages(IDLE_WAIT);
+            }
+            if let Err(error) = scope.run_event_loop(EVENT_LOOP_BUDGET) {
+                eprintln!("Unhandled error in Dart event loop: {}", error.pretty());
+            }
+        }
+        false
+    }
+
+    /// Replaces the app's isolate with a fresh one and runs the entry point again. The
+    /// script is loaded before the old isolate goes, so an app that doesn't compile keeps
+    /// running as it was.
+    fn restart(&mut self) -> anyhow::Result<()> {
+        println!("Restarting...");
+        let started = Instant::now();
+        let isolate = match self.runtime.load_script(
+            &self.script,
+            &self.package_config,
+            IsolateData::new(()).with_environment(self.environment.clone()),
+        ) {
+            Ok(isolate) => isolate,
+            Err(error) => {
+                eprintln!("Hot restart failed: {}", error.pretty());
+                return Ok(());
+            }
+        };
+        {
+            let scope = self.isolate.enter();
+            registry::detach_all(&scope);
+            window::release_windows(&scope);
+        }
+        std::mem::replace(&mut self.isolate, isolate).shutdown();
+        self.start()?;
+        println!("Restarted in {} ms", started.elapsed().as_millis());
+        Ok(())
+    }
+
+    fn shutdown(&mut self) {
+        // A reload in flight waits on the isolate, so let the watcher go once it's down.
+        if let Some(watcher) = &self.watcher {
+            watcher.stop();
+        }
+        {
+            let scope = self.isolate.enter();
+            window::release_windows(&scope);
+            registry::clear();
+        }
+        self.isolate.shutdown();
+        self.watcher = None;
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// The first Ctrl+C asks the running engine to stop after the current step, so it shuts
+/// down in order; a second one exits right away, for a Dart `main` that never returns.
+fn install_interrupt_handler() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let installed = ctrlc::set_handler(|| {
+            if INTERRUPTED.swap(true, Ordering::Relaxed) {
+                std::process::exit(130);
+            }
+            eprintln!("\nInterrupted. Shutting down...");
+        });
+        if let Err(error) = installed {
+            eprintln!("failed to set Ctrl+C handler: {}", error);
+        }
+    });
+}
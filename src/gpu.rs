@@ -1,4 +1,4 @@
-use bigfish_macros::native_impl;
+use bigfish_macros::{native_impl, NativePeer};
 use objc2::{rc::Retained, runtime::ProtocolObject};
 use objc2_metal::{
     MTL4AccelerationStructureDescriptor, MTL4AccelerationStructureGeometryDescriptor,
@@ -10,25 +10,40 @@ use objc2_metal::{
     MTL4RenderPassDescriptor, MTL4RenderPipelineDescriptor, MTL4VisibilityOptions,
     MTLAccelerationStructure, MTLAccelerationStructureDescriptor, MTLAttributeFormat,
     MTLBlendFactor, MTLColorWriteMask, MTLComputePipelineState, MTLCreateSystemDefaultDevice,
-    MTLDevice, MTLEvent, MTLIndexType, MTLLoadAction, MTLPixelFormat, MTLPrimitiveTopologyClass,
-    MTLPrimitiveType, MTLRenderPipelineState, MTLRenderStages, MTLResidencySet,
-    MTLResidencySetDescriptor, MTLSharedEvent, MTLSize, MTLStages, MTLStoreAction, MTLTexture,
-    MTLTextureDescriptor, MTLTextureType, MTLTextureUsage, MTLViewport,
+    MTLDevice, MTLEvent, MTLIndexType, MTLLibrary, MTLLoadAction, MTLPixelFormat,
+    MTLPrimitiveTopologyClass, MTLPrimitiveType, MTLRenderPipelineState, MTLRenderStages,
+    MTLResidencySet, MTLResidencySetDescriptor, MTLSharedEvent, MTLSize, MTLStages, MTLStoreAction,
+    MTLTexture, MTLTextureDescriptor, MTLTextureType, MTLTextureUsage, MTLViewport,
 };
 use std::process::{Command, Stdio};
 // Bring ObjC protocol traits into scope for method resolution.
 use objc2_foundation::NSArray;
 use objc2_metal::{
     MTL4ArgumentTable as _, MTL4CommandEncoder as _, MTL4Compiler as _,
-    MTL4RenderCommandEncoder as _, MTLBuffer as _, MTLDrawable as _, MTLTexture as _,
+    MTL4RenderCommandEncoder as _, MTLBuffer as _, MTLDrawable as _, MTLResource as _,
+    MTLTexture as _,
 };
 use objc2_quartz_core::CAMetalDrawable;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::dart_api::{from_dart, Handle, List, NativeArguments, Result, Scope, TypedDataView};
+use crate::dart_api::{
+    from_dart_arg, spawn_async, DartError, DartList, Handle, NativeArguments, NativeLibrary,
+    PersistentHandle, Result, Scope, SendPort, Serde, TypedDataElement, TypedDataView,
+};
+use crate::registry::{self, Retain};
+use crate::service::{ServiceExtension, ServiceParams, ServiceResult};
 use crate::window::Window;
 
+/// The Dart library declaring the GPU classes, whose natives are in the `gpu` namespace.
+const LIBRARY: &str = "package:app/gpu.dart";
+
+inventory::submit! {
+    NativeLibrary::new(LIBRARY, "gpu")
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 struct Vertex {
@@ -50,6 +65,8 @@ struct ViewportSize {
 
 type Id<T> = Retained<ProtocolObject<T>>;
 
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY)]
 struct Gpu {
     device: Id<dyn MTLDevice>,
     command_queue: Id<dyn MTL4CommandQueue>,
@@ -59,40 +76,134 @@ struct Gpu {
     residency_set: Id<dyn MTLResidencySet>,
     shared_event: Id<dyn MTLSharedEvent>,
     frame_number: u64,
-    window_peer: *mut Window,
+    /// The Dart `Window` presented to, kept open as long as the GPU. `None` while the GPU
+    /// is carried over a hot restart.
+    window: Option<PersistentHandle>,
 }
 
+impl Retain for Gpu {
+    fn detach(&mut self) {
+        self.window = None;
+    }
+}
+
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY)]
 struct CommandBuffer {
     drawable: Id<dyn CAMetalDrawable>,
 }
 
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY, external_size = "allocated_size")]
 struct Texture {
     texture: Id<dyn MTLTexture>,
+    _live: Live,
 }
 
+impl Texture {
+    fn new(texture: Id<dyn MTLTexture>) -> Self {
+        let _live = TEXTURES.track(texture.allocatedSize());
+        Self { texture, _live }
+    }
+
+    fn allocated_size(&self) -> usize {
+        self.texture.allocatedSize()
+    }
+}
+
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY)]
 struct AccelerationStructure {
     acceleration_structure: Id<dyn MTLAccelerationStructure>,
+    _live: Live,
+}
+
+/// How many GPU resources of a kind are alive, and the memory they hold, reported by
+/// `ext.bigfish.gpuResources`.
+struct LiveResources {
+    count: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+static BUFFERS: LiveResources = LiveResources::new();
+static TEXTURES: LiveResources = LiveResources::new();
+static ACCELERATION_STRUCTURES: LiveResources = LiveResources::new();
+static RENDER_PIPELINES: LiveResources = LiveResources::new();
+static COMPUTE_PIPELINES: LiveResources = LiveResources::new();
+
+impl LiveResources {
+    const fn new() -> Self {
+        Self {
+            count: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+        }
+    }
+
+    fn track(&'static self, bytes: usize) -> Live {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        Live {
+            resources: self,
+            bytes,
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "count": self.count.load(Ordering::Relaxed),
+            "bytes": self.bytes.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Keeps a resource counted in its [`LiveResources`] until the resource is dropped.
+struct Live {
+    resources: &'static LiveResources,
+    bytes: usize,
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        self.resources.count.fetch_sub(1, Ordering::Relaxed);
+        self.resources
+            .bytes
+            .fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// The GPU resources Dart holds on to.
+fn gpu_resources(_scope: &Scope<'_>, _params: &ServiceParams) -> ServiceResult {
+    Ok(json!({
+        "type": "GpuResources",
+        "buffers": BUFFERS.to_json(),
+        "textures": TEXTURES.to_json(),
+        "accelerationStructures": ACCELERATION_STRUCTURES.to_json(),
+        "renderPipelines": RENDER_PIPELINES.to_json(),
+        "computePipelines": COMPUTE_PIPELINES.to_json(),
+    }))
+}
+
+inventory::submit! {
+    ServiceExtension::isolate("ext.bigfish.gpuResources", gpu_resources)
 }
 
-#[native_impl]
+#[native_impl(namespace = "gpu")]
 impl Texture {
-    fn replace_region(args: NativeArguments) {
-        let texture_instance = args.get_arg(0).unwrap();
-        let texture = texture_instance.get_peer::<Texture>().unwrap();
-
-        let region_x = args.get_integer_arg(1).unwrap() as usize;
-        let region_y = args.get_integer_arg(2).unwrap() as usize;
-        let region_z = args.get_integer_arg(3).unwrap() as usize;
-        let region_width = args.get_integer_arg(4).unwrap() as usize;
-        let region_height = args.get_integer_arg(5).unwrap() as usize;
-        let region_depth = args.get_integer_arg(6).unwrap() as usize;
-        let mipmap_level = args.get_integer_arg(7).unwrap() as usize;
-
-        let data_handle = args.get_arg(8).unwrap();
-        let bytes_per_row = args.get_integer_arg(9).unwrap() as usize;
-        let bytes_per_image = args.get_integer_arg(10).unwrap_or(0) as usize;
-
-        let view = TypedDataView::acquire(data_handle).unwrap();
+    #[allow(clippy::too_many_arguments)]
+    #[dart(name = "_replaceRegion")]
+    fn replace_region(
+        texture: &Texture,
+        region_x: usize,
+        region_y: usize,
+        region_z: usize,
+        region_width: usize,
+        region_height: usize,
+        region_depth: usize,
+        mipmap_level: usize,
+        data: TypedDataView<'_>,
+        bytes_per_row: usize,
+        bytes_per_image: usize,
+    ) {
         let region = objc2_metal::MTLRegion {
             origin: objc2_metal::MTLOrigin {
                 x: region_x,
@@ -108,7 +219,7 @@ impl Texture {
 
         unsafe {
             use core::ptr::NonNull;
-            let bytes_ptr = NonNull::new(view.data).unwrap().cast::<core::ffi::c_void>();
+            let bytes_ptr = NonNull::new(data.data).unwrap().cast::<core::ffi::c_void>();
             texture
                 .texture
                 .replaceRegion_mipmapLevel_slice_withBytes_bytesPerRow_bytesPerImage(
@@ -120,27 +231,25 @@ impl Texture {
                     bytes_per_image,
                 );
         }
-        drop(view);
+        drop(data);
     }
 }
 
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY)]
 struct ArgumentTable {
     table: Id<dyn MTL4ArgumentTable>,
 }
 
-#[native_impl]
+#[native_impl(namespace = "gpu")]
 impl ArgumentTable {
-    fn set_buffer(args: NativeArguments) {
-        let argument_table_instance = args.get_arg(0).unwrap();
-        let argument_table = argument_table_instance.get_peer::<ArgumentTable>().unwrap();
-
-        let buffer_instance = args.get_arg(1).unwrap();
-        let buffer = buffer_instance.get_peer::<Buffer>().unwrap();
-
-        let index = args.get_integer_arg(2).unwrap() as usize;
-        let offset = args.get_integer_arg(3).unwrap_or(0) as usize;
-
-        let buffer_address = buffer.buffer.gpuAddress() + offset as u64;
+    fn set_buffer(
+        argument_table: &ArgumentTable,
+        buffer: &Buffer,
+        index: usize,
+        offset: Option<usize>,
+    ) {
+        let buffer_address = buffer.buffer.gpuAddress() + offset.unwrap_or(0) as u64;
         unsafe {
             argument_table
                 .table
@@ -148,15 +257,7 @@ impl ArgumentTable {
         }
     }
 
-    fn set_texture(args: NativeArguments) {
-        let argument_table_instance = args.get_arg(0).unwrap();
-        let argument_table = argument_table_instance.get_peer::<ArgumentTable>().unwrap();
-
-        let texture_instance = args.get_arg(1).unwrap();
-        let texture = texture_instance.get_peer::<Texture>().unwrap();
-
-        let index = args.get_integer_arg(2).unwrap() as usize;
-
+    fn set_texture(argument_table: &ArgumentTable, texture: &Texture, index: usize) {
         unsafe {
             let resource_id = texture.texture.gpuResourceID();
             argument_table.table.setTexture_atIndex(resource_id, index);
@@ -164,64 +265,54 @@ impl ArgumentTable {
     }
 }
 
-#[native_impl]
+#[native_impl(namespace = "gpu")]
 impl Texture {
-    fn width(args: NativeArguments) {
-        let texture_instance = args.get_arg(0).unwrap();
-        let texture = texture_instance.get_peer::<Texture>().unwrap();
-        let width = texture.texture.width() as i64;
-        args.set_int_return_value(width);
+    fn width(texture: &Texture) -> usize {
+        texture.texture.width()
     }
 
-    fn height(args: NativeArguments) {
-        let texture_instance = args.get_arg(0).unwrap();
-        let texture = texture_instance.get_peer::<Texture>().unwrap();
-        let height = texture.texture.height() as i64;
-        args.set_int_return_value(height);
+    fn height(texture: &Texture) -> usize {
+        texture.texture.height()
     }
 
-    fn pixel_format(args: NativeArguments) {
-        let texture_instance = args.get_arg(0).unwrap();
-        let texture = texture_instance.get_peer::<Texture>().unwrap();
-        let pixel_format = texture.texture.pixelFormat().0 as i64;
-        args.set_int_return_value(pixel_format);
+    fn pixel_format(texture: &Texture) -> usize {
+        texture.texture.pixelFormat().0
     }
 }
 
-#[native_impl]
+#[native_impl(namespace = "gpu")]
 impl CommandBuffer {
-    fn render_command_encoder(args: NativeArguments, scope: Scope<'_>) {
-        let command_buffer_instance = args.get_arg(0).unwrap();
-        let _command_buffer = command_buffer_instance.get_peer::<CommandBuffer>().unwrap();
-        let gpu_handle = command_buffer_instance
-            .get_field(scope.new_string("gpu").unwrap())
-            .unwrap();
-        let gpu = gpu_handle.get_peer::<Gpu>().unwrap();
-        let descriptor_instance = args.get_arg(1).unwrap();
-        let descriptor_map = descriptor_instance
-            .invoke(scope.new_string("toMap").unwrap(), &mut [])
-            .unwrap();
+    #[dart(name = "_renderCommandEncoder")]
+    fn render_command_encoder(
+        command_buffer: Handle<'_>,
+        #[dart(type = "RenderPassDescriptor")] descriptor: Handle<'_>,
+        scope: Scope<'_>,
+    ) -> Result<RenderCommandEncoder> {
+        command_buffer.peer::<CommandBuffer>()?;
+        let gpu_handle = command_buffer.get_field(scope.new_string("gpu")?)?;
+        let descriptor_map = descriptor.invoke(scope.new_string("toMap")?, &mut [])?;
 
         let pass = MTL4RenderPassDescriptor::new();
 
         // TODO: clean up this mess
-        let color_attachments_key = scope.new_string("colorAttachments").unwrap();
+        let color_attachments_key = scope.new_string("colorAttachments")?;
         if let Ok(color_attachments_list) = descriptor_map.map_get(&scope, color_attachments_key) {
-            let list_obj = List::new(color_attachments_list).unwrap();
+            let list_obj = DartList::try_from(color_attachments_list)?;
             if let Ok(len) = list_obj.len() {
                 for i in 0..(len as usize) {
                     if let Ok(ca_map) = list_obj.get(&scope, i as isize) {
                         let ca = unsafe { pass.colorAttachments().objectAtIndexedSubscript(i) };
 
-                        let texture_key = scope.new_string("texture").unwrap();
+                        let texture_key = scope.new_string("texture")?;
                         let texture = ca_map
                             .map_get(&scope, texture_key)
-                            .map(|h| h.get_peer::<Texture>().unwrap().texture.as_ref())
-                            .ok();
-                        ca.setTexture(texture);
+                            .ok()
+                            .map(|h| h.peer::<Texture>())
+                            .transpose()?;
+                        ca.setTexture(texture.as_ref().map(|texture| texture.texture.as_ref()));
 
                         // Extract load action
-                        let load_action_key = scope.new_string("loadAction").unwrap();
+                        let load_action_key = scope.new_string("loadAction")?;
                         if let Ok(load_action_handle) = ca_map.map_get(&scope, load_action_key) {
                             if let Ok(load_action_val) = load_action_handle.to_i64() {
                                 ca.setLoadAction(MTLLoadAction(load_action_val as usize));
@@ -229,7 +320,7 @@ impl CommandBuffer {
                         }
 
                         // Extract store action
-                        let store_action_key = scope.new_string("storeAction").unwrap();
+                        let store_action_key = scope.new_string("storeAction")?;
                         if let Ok(store_action_handle) = ca_map.map_get(&scope, store_action_key) {
                             if let Ok(store_action_val) = store_action_handle.to_i64() {
                                 ca.setStoreAction(MTLStoreAction(store_action_val as usize));
@@ -237,9 +328,9 @@ impl CommandBuffer {
                         }
 
                         // Extract clear color (optional)
-                        let clear_color_key = scope.new_string("clearColor").unwrap();
+                        let clear_color_key = scope.new_string("clearColor")?;
                         if let Ok(clear_color_list) = ca_map.map_get(&scope, clear_color_key) {
-                            let clear_color_list_obj = List::new(clear_color_list).unwrap();
+                            let clear_color_list_obj = DartList::try_from(clear_color_list)?;
                             if let Ok(clear_color_len) = clear_color_list_obj.len() {
                                 if clear_color_len >= 4 {
                                     if let (Ok(r), Ok(g), Ok(b), Ok(a)) = (
@@ -271,100 +362,65 @@ impl CommandBuffer {
             }
         }
 
+        let gpu = gpu_handle.peer::<Gpu>()?;
         let render_command_encoder = gpu
             .command_buffer
             .renderCommandEncoderWithDescriptor(&pass)
-            .unwrap();
-        let render_command_encoder_instance = scope
-            .new_object(
-                scope
-                    .get_class(
-                        scope.library("package:app/native.dart").unwrap(),
-                        "RenderCommandEncoder",
-                    )
-                    .unwrap(),
-                scope.null_handle().unwrap(),
-                &mut [],
-            )
-            .unwrap();
-        render_command_encoder_instance
-            .set_peer(Box::new(RenderCommandEncoder(render_command_encoder)));
-        args.set_return_value(render_command_encoder_instance);
+            .ok_or_else(|| DartError::Api("failed to create render command encoder".into()))?;
+        Ok(RenderCommandEncoder(render_command_encoder))
     }
 
-    fn compute_command_encoder(args: NativeArguments, scope: Scope<'_>) {
-        let command_buffer_instance = args.get_arg(0).unwrap();
-        let _command_buffer = command_buffer_instance.get_peer::<CommandBuffer>().unwrap();
-        let gpu_handle = command_buffer_instance
-            .get_field(scope.new_string("gpu").unwrap())
-            .unwrap();
-        let gpu = gpu_handle.get_peer::<Gpu>().unwrap();
-
-        let compute_command_encoder = gpu.command_buffer.computeCommandEncoder().unwrap();
-        let compute_command_encoder_instance = scope
-            .new_object(
-                scope
-                    .get_class(
-                        scope.library("package:app/native.dart").unwrap(),
-                        "ComputeCommandEncoder",
-                    )
-                    .unwrap(),
-                scope.null_handle().unwrap(),
-                &mut [],
-            )
-            .unwrap();
-        compute_command_encoder_instance
-            .set_peer(Box::new(ComputeCommandEncoder(compute_command_encoder)));
-        args.set_return_value(compute_command_encoder_instance);
-    }
+    fn compute_command_encoder(
+        command_buffer: Handle<'_>,
+        scope: Scope<'_>,
+    ) -> Result<ComputeCommandEncoder> {
+        command_buffer.peer::<CommandBuffer>()?;
+        let gpu_handle = command_buffer.get_field(scope.new_string("gpu")?)?;
+        let gpu = gpu_handle.peer::<Gpu>()?;
 
-    fn drawable(args: NativeArguments, scope: Scope<'_>) {
-        let command_buffer_instance = args.get_arg(0).unwrap();
-        let command_buffer = command_buffer_instance.get_peer::<CommandBuffer>().unwrap();
-        let texture = command_buffer.drawable.texture();
+        let compute_command_encoder = gpu
+            .command_buffer
+            .computeCommandEncoder()
+            .ok_or_else(|| DartError::Api("failed to create compute command encoder".into()))?;
+        Ok(ComputeCommandEncoder(compute_command_encoder))
+    }
 
-        let library = scope.library("package:app/native.dart").unwrap();
-        let class_type = scope.get_class(library, "Texture").unwrap();
-        let class_instance = scope
-            .new_object(class_type, scope.null_handle().unwrap(), &mut [])
-            .unwrap();
-        class_instance.set_peer(Box::new(Texture { texture }));
-        args.set_return_value(class_instance);
+    fn drawable(command_buffer: &CommandBuffer) -> Texture {
+        Texture::new(command_buffer.drawable.texture())
     }
 }
 
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY)]
 struct ComputeCommandEncoder(Id<dyn MTL4ComputeCommandEncoder>);
 
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY)]
 struct RenderCommandEncoder(Id<dyn MTL4RenderCommandEncoder>);
 
-#[native_impl]
+// Safety: the peers are only used on the isolate's thread. The finalizer may drop them on
+// another one, which only releases their Metal objects, and Metal objects can be released
+// from any thread.
+unsafe impl Send for Gpu {}
+unsafe impl Send for CommandBuffer {}
+unsafe impl Send for Texture {}
+unsafe impl Send for AccelerationStructure {}
+unsafe impl Send for ArgumentTable {}
+unsafe impl Send for ComputeCommandEncoder {}
+unsafe impl Send for RenderCommandEncoder {}
+unsafe impl Send for Buffer {}
+
+#[native_impl(namespace = "gpu")]
 impl RenderCommandEncoder {
-    fn set_render_pipeline(args: NativeArguments) {
-        let render_command_encoder_instance = args.get_arg(0).unwrap();
-        let render_command_encoder = render_command_encoder_instance
-            .get_peer::<RenderCommandEncoder>()
-            .unwrap();
-
-        let render_pipeline = args.get_arg(1).unwrap();
-        let render_pipeline = render_pipeline.get_peer::<RenderPipeline>().unwrap();
-
-        render_command_encoder
+    fn set_render_pipeline(encoder: &RenderCommandEncoder, render_pipeline: &RenderPipeline) {
+        encoder
             .0
             .setRenderPipelineState(&render_pipeline.render_pipeline_state);
     }
 
-    fn set_viewport(args: NativeArguments) {
-        let render_command_encoder_instance = args.get_arg(0).unwrap();
-        let render_command_encoder = render_command_encoder_instance
-            .get_peer::<RenderCommandEncoder>()
-            .unwrap();
-
-        let x = args.get_double_arg(1).unwrap();
-        let y = args.get_double_arg(2).unwrap();
-        let width = args.get_double_arg(3).unwrap();
-        let height = args.get_double_arg(4).unwrap();
-
-        render_command_encoder.0.setViewport(MTLViewport {
+    #[dart(name = "_setViewport")]
+    fn set_viewport(encoder: &RenderCommandEncoder, x: f64, y: f64, width: f64, height: f64) {
+        encoder.0.setViewport(MTLViewport {
             originX: x,
             originY: y,
             width,
@@ -374,89 +430,66 @@ impl RenderCommandEncoder {
         });
     }
 
-    fn set_scissor_rect(args: NativeArguments) {
-        let render_command_encoder_instance = args.get_arg(0).unwrap();
-        let render_command_encoder = render_command_encoder_instance
-            .get_peer::<RenderCommandEncoder>()
-            .unwrap();
-
-        let x = args.get_integer_arg(1).unwrap() as usize;
-        let y = args.get_integer_arg(2).unwrap() as usize;
-        let width = args.get_integer_arg(3).unwrap() as usize;
-        let height = args.get_integer_arg(4).unwrap() as usize;
-
-        render_command_encoder
-            .0
-            .setScissorRect(objc2_metal::MTLScissorRect {
-                x,
-                y,
-                width,
-                height,
-            });
-    }
-
-    fn set_cull_mode(args: NativeArguments) {
-        let render_command_encoder_instance = args.get_arg(0).unwrap();
-        let render_command_encoder = render_command_encoder_instance
-            .get_peer::<RenderCommandEncoder>()
-            .unwrap();
-
-        let mode = args.get_integer_arg(1).unwrap() as usize;
-        render_command_encoder
-            .0
-            .setCullMode(objc2_metal::MTLCullMode(mode));
+    #[dart(name = "_setScissorRect")]
+    fn set_scissor_rect(
+        encoder: &RenderCommandEncoder,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) {
+        encoder.0.setScissorRect(objc2_metal::MTLScissorRect {
+            x,
+            y,
+            width,
+            height,
+        });
     }
 
-    fn draw_primitives(args: NativeArguments) {
-        let render_command_encoder_instance = args.get_arg(0).unwrap();
-        let render_command_encoder = render_command_encoder_instance
-            .get_peer::<RenderCommandEncoder>()
-            .unwrap();
-        let primitive_type = args.get_integer_arg(1).unwrap();
-        let vertex_count = args.get_integer_arg(2).unwrap();
-        let instance_count = args.get_integer_arg(3).unwrap();
-        let vertex_start = args.get_integer_arg(4).unwrap();
-        let base_instance = args.get_integer_arg(5).unwrap();
+    #[dart(name = "_setCullMode")]
+    fn set_cull_mode(encoder: &RenderCommandEncoder, mode: usize) {
+        encoder.0.setCullMode(objc2_metal::MTLCullMode(mode));
+    }
 
+    #[dart(name = "_drawPrimitives")]
+    fn draw_primitives(
+        encoder: &RenderCommandEncoder,
+        primitive_type: usize,
+        vertex_count: usize,
+        instance_count: usize,
+        vertex_start: usize,
+        base_instance: usize,
+    ) {
         unsafe {
-            render_command_encoder
+            encoder
                 .0
                 .drawPrimitives_vertexStart_vertexCount_instanceCount_baseInstance(
-                    MTLPrimitiveType(primitive_type as usize),
-                    vertex_start as usize,
-                    vertex_count as usize,
-                    instance_count as usize,
-                    base_instance as usize,
+                    MTLPrimitiveType(primitive_type),
+                    vertex_start,
+                    vertex_count,
+                    instance_count,
+                    base_instance,
                 );
         }
     }
 
-    fn set_argument_table(args: NativeArguments) {
-        let render_command_encoder_instance = args.get_arg(0).unwrap();
-        let render_command_encoder = render_command_encoder_instance
-            .get_peer::<RenderCommandEncoder>()
-            .unwrap();
-
-        let argument_table_instance = args.get_arg(1).unwrap();
-        let argument_table = argument_table_instance.get_peer::<ArgumentTable>().unwrap();
-
+    fn set_argument_table(encoder: &RenderCommandEncoder, argument_table: &ArgumentTable) {
         unsafe {
-            render_command_encoder.0.setArgumentTable_atStages(
+            encoder.0.setArgumentTable_atStages(
                 argument_table.table.as_ref(),
                 MTLRenderStages::Vertex | MTLRenderStages::Fragment,
             );
         }
     }
 
-    fn intra_pass_barrier(args: NativeArguments) {
-        let render_command_encoder_instance = args.get_arg(0).unwrap();
-        let render_command_encoder = render_command_encoder_instance
-            .get_peer::<RenderCommandEncoder>()
-            .unwrap();
-        let after_encoder_stages = args.get_integer_arg(1).unwrap() as usize;
-        let before_encoder_stages = args.get_integer_arg(2).unwrap() as usize;
-        let visibility_options = args.get_integer_arg(3).unwrap() as usize;
-        render_command_encoder
+    #[dart(name = "_intraPassBarrier")]
+    fn intra_pass_barrier(
+        encoder: &RenderCommandEncoder,
+        after_encoder_stages: usize,
+        before_encoder_stages: usize,
+        visibility_options: usize,
+    ) {
+        encoder
             .0
             .barrierAfterEncoderStages_beforeEncoderStages_visibilityOptions(
                 MTLStages(after_encoder_stages),
@@ -465,15 +498,14 @@ impl RenderCommandEncoder {
             );
     }
 
-    fn consumer_barrier(args: NativeArguments) {
-        let render_command_encoder_instance = args.get_arg(0).unwrap();
-        let render_command_encoder = render_command_encoder_instance
-            .get_peer::<RenderCommandEncoder>()
-            .unwrap();
-        let after_encoder_stages = args.get_integer_arg(1).unwrap() as usize;
-        let before_encoder_stages = args.get_integer_arg(2).unwrap() as usize;
-        let visibility_options = args.get_integer_arg(3).unwrap() as usize;
-        render_command_encoder
+    #[dart(name = "_consumerBarrier")]
+    fn consumer_barrier(
+        encoder: &RenderCommandEncoder,
+        after_encoder_stages: usize,
+        before_encoder_stages: usize,
+        visibility_options: usize,
+    ) {
+        encoder
             .0
             .barrierAfterQueueStages_beforeStages_visibilityOptions(
                 MTLStages(after_encoder_stages),
@@ -482,16 +514,14 @@ impl RenderCommandEncoder {
             );
     }
 
-    fn producer_barrier(args: NativeArguments) {
-        let render_command_encoder_instance = args.get_arg(0).unwrap();
-        let render_command_encoder = render_command_encoder_instance
-            .get_peer::<RenderCommandEncoder>()
-            .unwrap();
-        let after_encoder_stages = args.get_integer_arg(1).unwrap() as usize;
-        let before_encoder_stages = args.get_integer_arg(2).unwrap() as usize;
-        let visibility_options = args.get_integer_arg(3).unwrap() as usize;
-
-        render_command_encoder
+    #[dart(name = "_producerBarrier")]
+    fn producer_barrier(
+        encoder: &RenderCommandEncoder,
+        after_encoder_stages: usize,
+        before_encoder_stages: usize,
+        visibility_options: usize,
+    ) {
+        encoder
             .0
             .barrierAfterStages_beforeQueueStages_visibilityOptions(
                 MTLStages(after_encoder_stages),
@@ -500,124 +530,89 @@ impl RenderCommandEncoder {
             );
     }
 
-    fn end_encoding(args: NativeArguments) {
-        let render_command_encoder_instance = args.get_arg(0).unwrap();
-        let render_command_encoder = render_command_encoder_instance
-            .get_peer::<RenderCommandEncoder>()
-            .unwrap();
-        render_command_encoder.0.endEncoding();
+    fn end_encoding(encoder: &RenderCommandEncoder) {
+        encoder.0.endEncoding();
     }
 }
 
-#[native_impl]
+#[native_impl(namespace = "gpu")]
 impl ComputeCommandEncoder {
-    fn set_compute_pipeline(args: NativeArguments) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-        let compute_pipeline = args.get_arg(1).unwrap();
-        let compute_pipeline = compute_pipeline.get_peer::<ComputePipeline>().unwrap();
-        compute_command_encoder
+    fn set_compute_pipeline(encoder: &ComputeCommandEncoder, compute_pipeline: &ComputePipeline) {
+        encoder
             .0
             .setComputePipelineState(&compute_pipeline.compute_pipeline_state);
     }
 
-    fn set_argument_table(args: NativeArguments) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-        let argument_table_instance = args.get_arg(1).unwrap();
-        let argument_table = argument_table_instance.get_peer::<ArgumentTable>().unwrap();
-        compute_command_encoder
+    fn set_argument_table(encoder: &ComputeCommandEncoder, argument_table: &ArgumentTable) {
+        encoder
             .0
             .setArgumentTable(Some(argument_table.table.as_ref()));
     }
 
-    fn dispatch_threads(args: NativeArguments) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-        let threads_per_grid_x = args.get_integer_arg(1).unwrap() as usize;
-        let threads_per_grid_y = args.get_integer_arg(2).unwrap() as usize;
-        let threads_per_grid_z = args.get_integer_arg(3).unwrap() as usize;
-        let threads_per_threadgroup_x = args.get_integer_arg(4).unwrap() as usize;
-        let threads_per_threadgroup_y = args.get_integer_arg(5).unwrap() as usize;
-        let threads_per_threadgroup_z = args.get_integer_arg(6).unwrap() as usize;
-
-        compute_command_encoder
-            .0
-            .dispatchThreads_threadsPerThreadgroup(
-                MTLSize {
-                    width: threads_per_grid_x,
-                    height: threads_per_grid_y,
-                    depth: threads_per_grid_z,
-                },
-                MTLSize {
-                    width: threads_per_threadgroup_x,
-                    height: threads_per_threadgroup_y,
-                    depth: threads_per_threadgroup_z,
-                },
-            );
+    /// threadsPerGrid - [texture.width, texture.height, texture.depth]
+    ///
+    /// threadsPerThreadgroupY - (numthreadgroups) [8, 8, 1]
+    ///
+    /// This should handle partial edges automatically
+    fn dispatch_threads(
+        encoder: &ComputeCommandEncoder,
+        threads_per_grid_x: usize,
+        threads_per_grid_y: usize,
+        threads_per_grid_z: usize,
+        threads_per_threadgroup_x: usize,
+        threads_per_threadgroup_y: usize,
+        threads_per_threadgroup_z: usize,
+    ) {
+        encoder.0.dispatchThreads_threadsPerThreadgroup(
+            MTLSize {
+                width: threads_per_grid_x,
+                height: threads_per_grid_y,
+                depth: threads_per_grid_z,
+            },
+            MTLSize {
+                width: threads_per_threadgroup_x,
+                height: threads_per_threadgroup_y,
+                depth: threads_per_threadgroup_z,
+            },
+        );
     }
 
-    fn dispatch_threadgroups(args: NativeArguments) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-        let threadgroups_per_grid_x = args.get_integer_arg(1).unwrap() as usize;
-        let threadgroups_per_grid_y = args.get_integer_arg(2).unwrap() as usize;
-        let threadgroups_per_grid_z = args.get_integer_arg(3).unwrap() as usize;
-        let threads_per_threadgroup_x = args.get_integer_arg(4).unwrap() as usize;
-        let threads_per_threadgroup_y = args.get_integer_arg(5).unwrap() as usize;
-        let threads_per_threadgroup_z = args.get_integer_arg(6).unwrap() as usize;
-
-        compute_command_encoder
-            .0
-            .dispatchThreadgroups_threadsPerThreadgroup(
-                MTLSize {
-                    width: threadgroups_per_grid_x,
-                    height: threadgroups_per_grid_y,
-                    depth: threadgroups_per_grid_z,
-                },
-                MTLSize {
-                    width: threads_per_threadgroup_x,
-                    height: threads_per_threadgroup_y,
-                    depth: threads_per_threadgroup_z,
-                },
-            );
+    fn dispatch_threadgroups(
+        encoder: &ComputeCommandEncoder,
+        threadgroups_per_grid_x: usize,
+        threadgroups_per_grid_y: usize,
+        threadgroups_per_grid_z: usize,
+        threads_per_threadgroup_x: usize,
+        threads_per_threadgroup_y: usize,
+        threads_per_threadgroup_z: usize,
+    ) {
+        encoder.0.dispatchThreadgroups_threadsPerThreadgroup(
+            MTLSize {
+                width: threadgroups_per_grid_x,
+                height: threadgroups_per_grid_y,
+                depth: threadgroups_per_grid_z,
+            },
+            MTLSize {
+                width: threads_per_threadgroup_x,
+                height: threads_per_threadgroup_y,
+                depth: threads_per_threadgroup_z,
+            },
+        );
     }
 
-    fn build_acceleration_structure(args: NativeArguments, scope: Scope<'_>) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-
-        let acceleration_structure_instance = args.get_arg(1).unwrap();
-        let acceleration_structure = acceleration_structure_instance
-            .get_peer::<AccelerationStructure>()
-            .unwrap();
-
-        let descriptor_instance = args.get_arg(2).unwrap();
-        let descriptor_map = descriptor_instance
-            .invoke(scope.new_string("toMap").unwrap(), &mut [])
-            .unwrap();
-        let descriptor = from_dart::<AccelerationStructureDescriptorData>(descriptor_map).unwrap();
+    #[dart(name = "_buildAccelerationStructure")]
+    fn build_acceleration_structure(
+        encoder: &ComputeCommandEncoder,
+        acceleration_structure: &AccelerationStructure,
+        #[dart(type = "AccelerationStructureDescriptor")]
+        descriptor: AccelerationStructureDescriptorData,
+        #[dart(type = "BufferRange")] scratch_buffer_range: BufferRangeData,
+    ) {
         let descriptor = build_mtl4_acceleration_structure_descriptor(descriptor);
-
-        let scratch_range_instance = args.get_arg(3).unwrap();
-        let scratch_range_map = scratch_range_instance
-            .invoke(scope.new_string("toMap").unwrap(), &mut [])
-            .unwrap();
-        let scratch_range = from_dart::<BufferRangeData>(scratch_range_map).unwrap();
-        let scratch_range = to_mtl4_buffer_range(&scratch_range);
+        let scratch_range = to_mtl4_buffer_range(&scratch_buffer_range);
 
         unsafe {
-            compute_command_encoder
+            encoder
                 .0
                 .buildAccelerationStructure_descriptor_scratchBuffer(
                     acceleration_structure.acceleration_structure.as_ref(),
@@ -627,15 +622,14 @@ impl ComputeCommandEncoder {
         }
     }
 
-    fn intra_pass_barrier(args: NativeArguments) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-        let after_encoder_stages = args.get_integer_arg(1).unwrap() as usize;
-        let before_encoder_stages = args.get_integer_arg(2).unwrap() as usize;
-        let visibility_options = args.get_integer_arg(3).unwrap() as usize;
-        compute_command_encoder
+    #[dart(name = "_intraPassBarrier")]
+    fn intra_pass_barrier(
+        encoder: &ComputeCommandEncoder,
+        after_encoder_stages: usize,
+        before_encoder_stages: usize,
+        visibility_options: usize,
+    ) {
+        encoder
             .0
             .barrierAfterEncoderStages_beforeEncoderStages_visibilityOptions(
                 MTLStages(after_encoder_stages),
@@ -644,15 +638,14 @@ impl ComputeCommandEncoder {
             );
     }
 
-    fn consumer_barrier(args: NativeArguments) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-        let after_encoder_stages = args.get_integer_arg(1).unwrap() as usize;
-        let before_encoder_stages = args.get_integer_arg(2).unwrap() as usize;
-        let visibility_options = args.get_integer_arg(3).unwrap() as usize;
-        compute_command_encoder
+    #[dart(name = "_consumerBarrier")]
+    fn consumer_barrier(
+        encoder: &ComputeCommandEncoder,
+        after_encoder_stages: usize,
+        before_encoder_stages: usize,
+        visibility_options: usize,
+    ) {
+        encoder
             .0
             .barrierAfterQueueStages_beforeStages_visibilityOptions(
                 MTLStages(after_encoder_stages),
@@ -661,16 +654,14 @@ impl ComputeCommandEncoder {
             );
     }
 
-    fn producer_barrier(args: NativeArguments) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-        let after_encoder_stages = args.get_integer_arg(1).unwrap() as usize;
-        let before_encoder_stages = args.get_integer_arg(2).unwrap() as usize;
-        let visibility_options = args.get_integer_arg(3).unwrap() as usize;
-
-        compute_command_encoder
+    #[dart(name = "_producerBarrier")]
+    fn producer_barrier(
+        encoder: &ComputeCommandEncoder,
+        after_encoder_stages: usize,
+        before_encoder_stages: usize,
+        visibility_options: usize,
+    ) {
+        encoder
             .0
             .barrierAfterStages_beforeQueueStages_visibilityOptions(
                 MTLStages(after_encoder_stages),
@@ -679,71 +670,68 @@ impl ComputeCommandEncoder {
             );
     }
 
-    fn copy(args: NativeArguments) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-        let source_texture_instance = args.get_arg(1).unwrap();
-        let source_texture = source_texture_instance.get_peer::<Texture>().unwrap();
-        let destination_texture_instance = args.get_arg(2).unwrap();
-        let destination_texture = destination_texture_instance.get_peer::<Texture>().unwrap();
+    fn copy(
+        encoder: &ComputeCommandEncoder,
+        source_texture: &Texture,
+        destination_texture: &Texture,
+    ) {
         unsafe {
-            compute_command_encoder.0.copyFromTexture_toTexture(
+            encoder.0.copyFromTexture_toTexture(
                 source_texture.texture.as_ref(),
                 destination_texture.texture.as_ref(),
             );
         }
     }
 
-    fn generate_mipmaps(args: NativeArguments) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-        let texture_instance = args.get_arg(1).unwrap();
-        let texture = texture_instance.get_peer::<Texture>().unwrap();
+    fn generate_mipmaps(encoder: &ComputeCommandEncoder, texture: &Texture) {
         unsafe {
             // compute_command_encoder.0.barrier
-            compute_command_encoder
+            encoder
                 .0
                 .generateMipmapsForTexture(texture.texture.as_ref());
         }
     }
 
-    fn end_encoding(args: NativeArguments) {
-        let compute_command_encoder_instance = args.get_arg(0).unwrap();
-        let compute_command_encoder = compute_command_encoder_instance
-            .get_peer::<ComputeCommandEncoder>()
-            .unwrap();
-        compute_command_encoder.0.endEncoding();
+    fn end_encoding(encoder: &ComputeCommandEncoder) {
+        encoder.0.endEncoding();
     }
 }
-#[native_impl]
+#[native_impl(namespace = "gpu")]
 impl Gpu {
-    fn init(args: NativeArguments) {
-        let instance = args.get_arg(0).unwrap();
-        let window_handle = args.get_arg(1).unwrap();
-        let window_peer = window_handle.get_peer::<Window>().unwrap() as *mut Window;
-
+    #[dart(name = "_initGpu")]
+    fn init(gpu: Handle<'_>, #[dart(type = "Window")] window: Handle<'_>) -> Result<()> {
         let frames_in_flight = 3;
-        let device = MTLCreateSystemDefaultDevice().unwrap();
-        let command_queue = device.newMTL4CommandQueue().unwrap();
-        let command_buffer = device.newCommandBuffer().unwrap();
+        let device = MTLCreateSystemDefaultDevice()
+            .ok_or_else(|| DartError::Api("no Metal device available".into()))?;
+        let command_queue = device
+            .newMTL4CommandQueue()
+            .ok_or_else(|| DartError::Api("failed to create command queue".into()))?;
+        let command_buffer = device
+            .newCommandBuffer()
+            .ok_or_else(|| DartError::Api("failed to create command buffer".into()))?;
         let mut command_allocators = Vec::with_capacity(frames_in_flight);
         for _ in 0..frames_in_flight {
-            let command_allocator = device.newCommandAllocator().unwrap();
+            let command_allocator = device
+                .newCommandAllocator()
+                .ok_or_else(|| DartError::Api("failed to create command allocator".into()))?;
             command_allocators.push(command_allocator);
         }
         let desc = MTLResidencySetDescriptor::new();
-        let residency_set = device.newResidencySetWithDescriptor_error(&desc).unwrap();
+        let residency_set = device
+            .newResidencySetWithDescriptor_error(&desc)
+            .map_err(|error| {
+                DartError::Api(format!(
+                    "failed to create residency set: {}",
+                    error.localizedDescription()
+                ))
+            })?;
 
         // Bind the SDL-created CAMetalLayer to this device and configure basics.
         #[cfg(target_os = "macos")]
         {
             use objc2_quartz_core::CAMetalLayer;
-            let window = unsafe { &*window_peer };
-            let layer: &CAMetalLayer = window.metal_layer();
+            let window = window.peer::<Window>()?;
+            let layer: &CAMetalLayer = window.metal_layer()?;
             layer.setDevice(Some(device.as_ref()));
             layer.setPixelFormat(MTLPixelFormat::BGRA8Unorm);
             layer.setMaximumDrawableCount(frames_in_flight);
@@ -753,19 +741,26 @@ impl Gpu {
 
         #[cfg(target_os = "macos")]
         {
-            let window = unsafe { &*window_peer };
-            command_queue.addResidencySet(&window.metal_layer().residencySet());
+            let window = window.peer::<Window>()?;
+            command_queue.addResidencySet(&window.metal_layer()?.residencySet());
         }
 
-        let shared_event = device.newSharedEvent().unwrap();
+        let shared_event = device
+            .newSharedEvent()
+            .ok_or_else(|| DartError::Api("failed to create shared event".into()))?;
         shared_event.setSignaledValue(0);
 
         let compiler_desc = MTL4CompilerDescriptor::new();
         let compiler = device
             .newCompilerWithDescriptor_error(&compiler_desc)
-            .unwrap();
-
-        instance.set_peer(Box::new(Gpu {
+            .map_err(|error| {
+                DartError::Api(format!(
+                    "failed to create shader compiler: {}",
+                    error.localizedDescription()
+                ))
+            })?;
+
+        gpu.set_peer(Box::new(Gpu {
             device,
             command_queue,
             command_buffer,
@@ -774,17 +769,37 @@ impl Gpu {
             compiler,
             shared_event,
             frame_number: 0,
-            window_peer,
-        }));
+            window: Some(PersistentHandle::new(window)?),
+        }))
     }
 
-    fn create_argument_table(args: NativeArguments, scope: Scope<'_>) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
+    /// Keeps the device, queues and residency set across hot restarts under `name`.
+    #[dart(name = "_retain")]
+    fn retain(gpu: Handle<'_>, name: String) -> Result<()> {
+        registry::retain::<Gpu>(gpu, &name)
+    }
 
-        let max_buffer_bind_count = args.get_integer_arg(1).unwrap() as usize;
-        let max_texture_bind_count = args.get_integer_arg(2).unwrap() as usize;
-        let max_sampler_state_bind_count = args.get_integer_arg(3).unwrap() as usize;
+    /// Takes over the GPU the previous run retained under `name`, presenting to `window`.
+    #[dart(name = "_attach")]
+    fn attach(
+        gpu: Handle<'_>,
+        name: String,
+        #[dart(type = "Window")] window: Handle<'_>,
+    ) -> Result<bool> {
+        if !registry::attach::<Gpu>(gpu, &name)? {
+            return Ok(false);
+        }
+        gpu.peer_mut::<Gpu>()?.window = Some(PersistentHandle::new(window)?);
+        Ok(true)
+    }
+
+    #[dart(name = "_createArgumentTable")]
+    fn create_argument_table(
+        gpu: &Gpu,
+        max_buffer_bind_count: usize,
+        max_texture_bind_count: usize,
+        max_sampler_state_bind_count: usize,
+    ) -> Result<ArgumentTable> {
         let table_desc = MTL4ArgumentTableDescriptor::new();
         if max_buffer_bind_count > 0 {
             table_desc.setMaxBufferBindCount(max_buffer_bind_count);
@@ -798,25 +813,32 @@ impl Gpu {
         let table = gpu
             .device
             .newArgumentTableWithDescriptor_error(&table_desc)
-            .unwrap();
-
-        let library = scope.library("package:app/native.dart").unwrap();
-        let class_type = scope.get_class(library, "ArgumentTable").unwrap();
-        let class_instance = scope
-            .new_object(class_type, scope.null_handle().unwrap(), &mut [])
-            .unwrap();
-        class_instance.set_peer(Box::new(ArgumentTable { table }));
-        args.set_return_value(class_instance);
+            .map_err(|error| {
+                DartError::Api(format!(
+                    "failed to create argument table: {}",
+                    error.localizedDescription()
+                ))
+            })?;
+
+        Ok(ArgumentTable { table })
     }
 
-    fn begin_command_buffer(args: NativeArguments, scope: Scope<'_>) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu: &mut Gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let window = unsafe { &*gpu.window_peer };
-
-        let drawable = match window.metal_layer().nextDrawable() {
+    #[dart(signature = "CommandBuffer beginCommandBuffer()")]
+    fn begin_command_buffer<'s>(
+        gpu_instance: Handle<'_>,
+        scope: &Scope<'s>,
+    ) -> Result<Option<Handle<'s>>> {
+        let mut gpu = gpu_instance.peer_mut::<Gpu>()?;
+        let window_handle = gpu
+            .window
+            .as_ref()
+            .ok_or_else(|| DartError::Api("the GPU has no window".into()))?
+            .get(scope)?;
+        let window = window_handle.peer::<Window>()?;
+
+        let drawable = match window.metal_layer()?.nextDrawable() {
             Some(d) => d,
-            None => return,
+            None => return Ok(None),
         };
 
         gpu.frame_number += 1;
@@ -835,28 +857,18 @@ impl Gpu {
         gpu.command_buffer
             .beginCommandBufferWithAllocator(allocator);
         gpu.command_buffer.useResidencySet(&gpu.residency_set);
-
-        let library = scope.library("package:app/native.dart").unwrap();
-        let class_type = scope.get_class(library, "CommandBuffer").unwrap();
-        // let constructor_name = scope.new_string("CommandBuffer").unwrap();
-        let class_instance = scope
-            .new_object(
-                class_type,
-                scope.null_handle().unwrap(),
-                &mut [gpu_instance.raw()],
-            )
-            .unwrap();
-        class_instance.set_peer(Box::new(CommandBuffer { drawable }));
-        // class_instance.set_field(scope.new_string("gpu").unwrap(), &gpu_instance);
-        args.set_return_value(class_instance);
+        // The constructor runs Dart code, which may use the GPU or window again.
+        drop(window);
+        drop(gpu);
+
+        let class_type = scope.class(LIBRARY, "CommandBuffer")?;
+        let class_instance =
+            scope.new_object(class_type, scope.null_handle()?, &mut [gpu_instance.raw()])?;
+        class_instance.set_peer(Box::new(CommandBuffer { drawable }))?;
+        Ok(Some(class_instance))
     }
 
-    fn end_command_buffer(args: NativeArguments) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let command_buffer_instance = args.get_arg(1).unwrap();
-        let command_buffer = command_buffer_instance.get_peer::<CommandBuffer>().unwrap();
-
+    fn end_command_buffer(gpu: &Gpu, command_buffer: &CommandBuffer) {
         gpu.command_buffer.endCommandBuffer();
 
         // Submit + present (Metal 4 queue semantics).
@@ -878,200 +890,56 @@ impl Gpu {
         gpu.command_queue.signalEvent_value(event, gpu.frame_number);
     }
 
-    fn compile_render_pipeline(args: NativeArguments, scope: Scope<'_>) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let descriptor_instance = args.get_arg(1).unwrap();
-        let descriptor = descriptor_instance
-            .invoke(scope.new_string("toMap").unwrap(), &mut [])
-            .unwrap();
-        let descriptor = from_dart::<RenderPipelineDescriptor>(descriptor).unwrap();
-        let rp_desc = MTL4RenderPipelineDescriptor::new();
-        for i in 0..descriptor.color_attachments.len() {
-            let color_attachment = &descriptor.color_attachments[i];
-            let ca = unsafe { rp_desc.colorAttachments().objectAtIndexedSubscript(i) };
-            ca.setPixelFormat(MTLPixelFormat(color_attachment.pixel_format.0));
-            ca.setWriteMask(MTLColorWriteMask(color_attachment.write_mask.0));
-            ca.setBlendingState(if color_attachment.blend_enabled {
-                MTL4BlendState::Enabled
-            } else {
-                MTL4BlendState::Disabled
-            });
-            ca.setSourceRGBBlendFactor(MTLBlendFactor(color_attachment.source_rgb_blend_factor.0));
-            ca.setDestinationRGBBlendFactor(MTLBlendFactor(
-                color_attachment.destination_rgb_blend_factor.0,
-            ));
-            ca.setSourceAlphaBlendFactor(MTLBlendFactor(
-                color_attachment.source_alpha_blend_factor.0,
-            ));
-            ca.setDestinationAlphaBlendFactor(MTLBlendFactor(
-                color_attachment.destination_alpha_blend_factor.0,
-            ));
-        }
-
-        rp_desc
-            .setInputPrimitiveTopology(MTLPrimitiveTopologyClass(descriptor.primitive_topology.0));
-
-        let vertex_spirv = {
-            let child = Command::new("slangc")
-                .arg("-stage")
-                .arg("vertex")
-                .arg("-target")
-                .arg("spirv")
-                .arg("-entry")
-                .arg(descriptor.vertex_shader.entry_point.as_str())
-                .arg(descriptor.vertex_shader.path.as_str())
-                .stdout(Stdio::piped())
-                .spawn()
-                .unwrap();
-            let output = child.wait_with_output().unwrap();
-            output.stdout
-        };
-
-        let fragment_spirv = {
-            let child = Command::new("slangc")
-                .arg("-stage")
-                .arg("fragment")
-                .arg("-target")
-                .arg("spirv")
-                .arg("-entry")
-                .arg(descriptor.fragment_shader.entry_point.as_str())
-                .arg(descriptor.fragment_shader.path.as_str())
-                .stdout(Stdio::piped())
-                .spawn()
-                .unwrap();
-            let output = child.wait_with_output().unwrap();
-            output.stdout
-        };
-
-        let vertex_metal = {
-            let mut child = Command::new("spirv-cross")
-                .arg("-")
-                .arg("--msl")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()
-                .unwrap();
-
-            if let Some(mut stdin) = child.stdin.take() {
-                stdin.write_all(&vertex_spirv).unwrap();
-                stdin.flush().unwrap();
-            }
-
-            let output = child.wait_with_output().unwrap();
-            String::from_utf8(output.stdout).unwrap()
-        };
-
-        let fragment_metal = {
-            let mut child = Command::new("spirv-cross")
-                .arg("-")
-                .arg("--msl")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()
-                .unwrap();
-
-            use std::io::Write;
-            if let Some(mut stdin) = child.stdin.take() {
-                stdin.write_all(&fragment_spirv).unwrap();
-                stdin.flush().unwrap();
-            }
-
-            let output = child.wait_with_output().unwrap();
-            String::from_utf8(output.stdout).unwrap()
-        };
-
-        std::fs::write("target/shaders/vertex.metal", &vertex_metal).unwrap();
-        std::fs::write("target/shaders/fragment.metal", &fragment_metal).unwrap();
-
-        let vertex_library = gpu
-            .device
-            .newLibraryWithSource_options_error(
-                &objc2_foundation::NSString::from_str(&vertex_metal),
-                None,
-            )
-            .unwrap();
-        let fragment_library = gpu
-            .device
-            .newLibraryWithSource_options_error(
-                &objc2_foundation::NSString::from_str(&fragment_metal),
-                None,
-            )
-            .unwrap();
-        let vfd = objc2_metal::MTL4LibraryFunctionDescriptor::new();
-        vfd.setLibrary(Some(&vertex_library));
-        vfd.setName(Some(&objc2_foundation::NSString::from_str("main0")));
-        rp_desc.setVertexFunctionDescriptor(Some(&*vfd));
+    #[dart(signature = "RenderPipeline compileRenderPipeline(RenderPipelineDescriptor descriptor)")]
+    fn compile_render_pipeline(args: NativeArguments, scope: Scope<'_>) -> Result<()> {
+        let gpu_instance = args.get_arg(0)?;
+        let descriptor = from_dart_arg::<RenderPipelineDescriptor>(&args, 1)?;
+        let (vertex_metal, fragment_metal) = compile_render_shaders(&descriptor)?;
+        let render_pipeline = new_render_pipeline(
+            &scope,
+            gpu_instance,
+            &descriptor,
+            &vertex_metal,
+            &fragment_metal,
+        )?;
+        args.set_return_value(render_pipeline);
+        Ok(())
+    }
 
-        let ffd = objc2_metal::MTL4LibraryFunctionDescriptor::new();
-        ffd.setLibrary(Some(&fragment_library));
-        ffd.setName(Some(&objc2_foundation::NSString::from_str("main0")));
-        rp_desc.setFragmentFunctionDescriptor(Some(&*ffd));
+    /// Compiles the shaders on a worker thread so the frame loop keeps running, then
+    /// creates the pipeline state back on the isolate thread.
+    #[dart(name = "_compileRenderPipelineAsync")]
+    fn compile_render_pipeline_async(
+        gpu: Handle<'_>,
+        port: SendPort,
+        descriptor: RenderPipelineDescriptor,
+    ) -> Result<()> {
+        let gpu = PersistentHandle::new(gpu)?;
+        spawn_async(
+            port,
+            move || {
+                let shaders = compile_render_shaders(&descriptor)?;
+                Ok((descriptor, shaders))
+            },
+            move |scope, (descriptor, (vertex_metal, fragment_metal))| {
+                new_render_pipeline(
+                    scope,
+                    gpu.get(scope)?,
+                    &descriptor,
+                    &vertex_metal,
+                    &fragment_metal,
+                )
+            },
+        )
+    }
 
-        let render_pipeline_state = gpu
-            .compiler
-            .newRenderPipelineStateWithDescriptor_compilerTaskOptions_error(&rp_desc, None)
-            .unwrap();
-
-        let library = scope.library("package:app/native.dart").unwrap();
-        let class_type = scope.get_class(library, "RenderPipeline").unwrap();
-        let class_instance = scope
-            .new_object(class_type, scope.null_handle().unwrap(), &mut [])
-            .unwrap();
-        class_instance.set_peer(Box::new(RenderPipeline {
-            render_pipeline_state,
-        }));
-        class_instance.set_field(scope.new_string("gpu").unwrap(), &gpu_instance);
-        args.set_return_value(class_instance);
-    }
-
-    fn compile_compute_pipeline(args: NativeArguments, scope: Scope<'_>) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let descriptor_instance = args.get_arg(1).unwrap();
-        let descriptor = descriptor_instance
-            .invoke(scope.new_string("toMap").unwrap(), &mut [])
-            .unwrap();
-        let descriptor = from_dart::<ComputePipelineDescriptor>(descriptor).unwrap();
-        let compute_shader = descriptor.compute_shader;
-        let compute_shader_spirv = {
-            let child = Command::new("slangc")
-                .arg("-stage")
-                .arg("compute")
-                .arg("-target")
-                .arg("spirv")
-                .arg("-entry")
-                .arg(compute_shader.entry_point.as_str())
-                .arg(compute_shader.path.as_str())
-                .stdout(Stdio::piped())
-                .spawn()
-                .unwrap();
-            let output = child.wait_with_output().unwrap();
-            output.stdout
-        };
-        let compute_shader_metal = {
-            let mut child = Command::new("spirv-cross")
-                .arg("-")
-                .arg("--msl")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()
-                .unwrap();
-            if let Some(mut stdin) = child.stdin.take() {
-                stdin.write_all(&compute_shader_spirv).unwrap();
-                stdin.flush().unwrap();
-            }
-            let output = child.wait_with_output().unwrap();
-            String::from_utf8(output.stdout).unwrap()
-        };
-        std::fs::write("target/shaders/compute.metal", &compute_shader_metal).unwrap();
-        let compute_shader_library = gpu
-            .device
-            .newLibraryWithSource_options_error(
-                &objc2_foundation::NSString::from_str(&compute_shader_metal),
-                None,
-            )
-            .unwrap();
+    fn compile_compute_pipeline(
+        gpu: &Gpu,
+        descriptor: ComputePipelineDescriptor,
+    ) -> Result<ComputePipeline> {
+        let compute_shader_metal = compile_shader_to_msl("compute", &descriptor.compute_shader)?;
+        write_shader_dump("compute", &descriptor.compute_shader, &compute_shader_metal)?;
+        let compute_shader_library = new_library(gpu, &compute_shader_metal)?;
 
         let cfd = objc2_metal::MTL4LibraryFunctionDescriptor::new();
         cfd.setLibrary(Some(&compute_shader_library));
@@ -1083,23 +951,20 @@ impl Gpu {
         let compute_pipeline_state = gpu
             .compiler
             .newComputePipelineStateWithDescriptor_compilerTaskOptions_error(&desc, None)
-            .unwrap();
-
-        let library = scope.library("package:app/native.dart").unwrap();
-        let class_type = scope.get_class(library, "ComputePipeline").unwrap();
-        let class_instance = scope
-            .new_object(class_type, scope.null_handle().unwrap(), &mut [])
-            .unwrap();
-        class_instance.set_peer(Box::new(ComputePipeline {
+            .map_err(|error| {
+                DartError::Api(format!(
+                    "failed to create compute pipeline state: {}",
+                    error.localizedDescription()
+                ))
+            })?;
+
+        Ok(ComputePipeline {
             compute_pipeline_state,
-        }));
-        args.set_return_value(class_instance);
+            _live: COMPUTE_PIPELINES.track(0),
+        })
     }
 
-    fn create_buffer(args: NativeArguments, scope: Scope<'_>) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let length = args.get_integer_arg(1).unwrap() as usize;
+    fn create_buffer(gpu: &Gpu, length: usize) -> Buffer {
         // For now, always use StorageModeShared
         // TODO: Support other storage modes if needed
         let options = objc2_metal::MTLResourceOptions::StorageModeShared;
@@ -1108,61 +973,35 @@ impl Gpu {
             .newBufferWithLength_options(length, options)
             .unwrap();
 
-        let library = scope.library("package:app/native.dart").unwrap();
-        let class_type = scope.get_class(library, "Buffer").unwrap();
-        let class_instance = scope
-            .new_object(class_type, scope.null_handle().unwrap(), &mut [])
-            .unwrap();
-        class_instance.set_peer(Box::new(Buffer { buffer }));
-        args.set_return_value(class_instance);
+        Buffer::new(buffer)
     }
 
-    fn add_buffer_to_residency_set(args: NativeArguments) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let buffer_instance = args.get_arg(1).unwrap();
-        let buffer = buffer_instance.get_peer::<Buffer>().unwrap();
-
+    fn add_buffer_to_residency_set(gpu: &Gpu, buffer: &Buffer) {
         gpu.residency_set.addAllocation(buffer.buffer.as_ref());
     }
 
-    fn add_texture_to_residency_set(args: NativeArguments) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let texture_instance = args.get_arg(1).unwrap();
-        let texture = texture_instance.get_peer::<Texture>().unwrap();
-
+    fn add_texture_to_residency_set(gpu: &Gpu, texture: &Texture) {
         gpu.residency_set.addAllocation(texture.texture.as_ref());
     }
 
-    fn add_acceleration_structure_to_residency_set(args: NativeArguments) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let acceleration_structure_instance = args.get_arg(1).unwrap();
-        let acceleration_structure = acceleration_structure_instance
-            .get_peer::<AccelerationStructure>()
-            .unwrap();
-
+    fn add_acceleration_structure_to_residency_set(
+        gpu: &Gpu,
+        acceleration_structure: &AccelerationStructure,
+    ) {
         gpu.residency_set
             .addAllocation(acceleration_structure.acceleration_structure.as_ref());
     }
 
-    fn commit_residency_set(args: NativeArguments) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-
+    fn commit_residency_set(gpu: &Gpu) {
         gpu.residency_set.commit();
     }
 
-    fn acceleration_structure_sizes(args: NativeArguments, scope: Scope<'_>) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let descriptor_instance = args.get_arg(1).unwrap();
-        let descriptor_map = descriptor_instance
-            .invoke(scope.new_string("toMap").unwrap(), &mut [])
-            .unwrap();
-        let descriptor = from_dart::<AccelerationStructureDescriptorData>(descriptor_map).unwrap();
-
+    #[dart(name = "_accelerationStructureSizes", returns = "Map<String, dynamic>")]
+    fn acceleration_structure_sizes(
+        gpu: &Gpu,
+        #[dart(type = "AccelerationStructureDescriptor")]
+        descriptor: AccelerationStructureDescriptorData,
+    ) -> Serde<AccelerationStructureSizesData> {
         let mtl4_descriptor = build_mtl4_acceleration_structure_descriptor(descriptor);
         let mtl_descriptor: Retained<MTLAccelerationStructureDescriptor> =
             mtl4_descriptor.clone().into_super();
@@ -1170,66 +1009,30 @@ impl Gpu {
             .device
             .accelerationStructureSizesWithDescriptor(mtl_descriptor.as_ref());
 
-        let library = scope.library("package:app/native.dart").unwrap();
-        let class_type = scope
-            .get_class(library, "AccelerationStructureSizes")
-            .unwrap();
-        let class_instance = scope
-            .new_object(class_type, scope.null_handle().unwrap(), &mut [])
-            .unwrap();
-        class_instance.set_field(
-            scope.new_string("accelerationStructureSize").unwrap(),
-            &scope
-                .new_integer(sizes.accelerationStructureSize as i64)
-                .unwrap(),
-        );
-        class_instance.set_field(
-            scope.new_string("buildScratchBufferSize").unwrap(),
-            &scope
-                .new_integer(sizes.buildScratchBufferSize as i64)
-                .unwrap(),
-        );
-        class_instance.set_field(
-            scope.new_string("refitScratchBufferSize").unwrap(),
-            &scope
-                .new_integer(sizes.refitScratchBufferSize as i64)
-                .unwrap(),
-        );
-        args.set_return_value(class_instance);
+        Serde(AccelerationStructureSizesData {
+            acceleration_structure_size: sizes.accelerationStructureSize as u64,
+            build_scratch_buffer_size: sizes.buildScratchBufferSize as u64,
+            refit_scratch_buffer_size: sizes.refitScratchBufferSize as u64,
+        })
     }
 
-    fn create_acceleration_structure(args: NativeArguments, scope: Scope<'_>) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let size = args.get_integer_arg(1).unwrap() as usize;
-
+    fn create_acceleration_structure(gpu: &Gpu, size: usize) -> AccelerationStructure {
         let acceleration_structure = gpu.device.newAccelerationStructureWithSize(size).unwrap();
 
-        let library = scope.library("package:app/native.dart").unwrap();
-        let class_type = scope.get_class(library, "AccelerationStructure").unwrap();
-        let class_instance = scope
-            .new_object(class_type, scope.null_handle().unwrap(), &mut [])
-            .unwrap();
-        class_instance.set_peer(Box::new(AccelerationStructure {
+        let _live = ACCELERATION_STRUCTURES.track(acceleration_structure.allocatedSize());
+        AccelerationStructure {
             acceleration_structure,
-        }));
-        args.set_return_value(class_instance);
+            _live,
+        }
     }
 
-    fn create_texture(args: NativeArguments, scope: Scope<'_>) {
-        let gpu_instance = args.get_arg(0).unwrap();
-        let gpu = gpu_instance.get_peer::<Gpu>().unwrap();
-        let width = args.get_integer_arg(1).unwrap() as usize;
-        let height = args.get_integer_arg(2).unwrap() as usize;
-        let pixel_format_value = args.get_integer_arg(3).unwrap() as usize;
-        let pixel_format = MTLPixelFormat(pixel_format_value);
-
+    fn create_texture(gpu: &Gpu, width: usize, height: usize, pixel_format: usize) -> Texture {
         let descriptor = MTLTextureDescriptor::new();
         unsafe {
             descriptor.setTextureType(MTLTextureType::Type2D);
             descriptor.setWidth(width);
             descriptor.setHeight(height);
-            descriptor.setPixelFormat(pixel_format);
+            descriptor.setPixelFormat(MTLPixelFormat(pixel_format));
             descriptor.setStorageMode(objc2_metal::MTLStorageMode::Managed);
             descriptor.setUsage(
                 MTLTextureUsage::ShaderRead
@@ -1241,123 +1044,316 @@ impl Gpu {
 
         let texture = gpu.device.newTextureWithDescriptor(&descriptor).unwrap();
 
-        let library = scope.library("package:app/native.dart").unwrap();
-        let class_type = scope.get_class(library, "Texture").unwrap();
-        let class_instance = scope
-            .new_object(class_type, scope.null_handle().unwrap(), &mut [])
-            .unwrap();
-        class_instance.set_peer(Box::new(Texture { texture }));
-        args.set_return_value(class_instance);
+        Texture::new(texture)
     }
 }
 
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY)]
 struct RenderPipeline {
     render_pipeline_state: Id<dyn MTLRenderPipelineState>,
+    _live: Live,
 }
 
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY)]
 struct ComputePipeline {
     compute_pipeline_state: Id<dyn MTLComputePipelineState>,
+    _live: Live,
+}
+
+impl Retain for RenderPipeline {}
+
+impl Retain for ComputePipeline {}
+
+#[native_impl(namespace = "gpu")]
+impl RenderPipeline {
+    /// Keeps the compiled pipeline across hot restarts under `name`.
+    #[dart(name = "_retain")]
+    fn retain(pipeline: Handle<'_>, name: String) -> Result<()> {
+        registry::retain::<RenderPipeline>(pipeline, &name)
+    }
+
+    /// Takes over the pipeline the previous run retained under `name`, if there is one.
+    #[dart(name = "_attach")]
+    fn attach(pipeline: Handle<'_>, name: String) -> Result<bool> {
+        registry::attach::<RenderPipeline>(pipeline, &name)
+    }
 }
 
+#[native_impl(namespace = "gpu")]
+impl ComputePipeline {
+    /// Keeps the compiled pipeline across hot restarts under `name`.
+    #[dart(name = "_retain")]
+    fn retain(pipeline: Handle<'_>, name: String) -> Result<()> {
+        registry::retain::<ComputePipeline>(pipeline, &name)
+    }
+
+    /// Takes over the pipeline the previous run retained under `name`, if there is one.
+    #[dart(name = "_attach")]
+    fn attach(pipeline: Handle<'_>, name: String) -> Result<bool> {
+        registry::attach::<ComputePipeline>(pipeline, &name)
+    }
+}
+
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY, external_size = "allocated_size")]
 struct Buffer {
     buffer: Id<dyn objc2_metal::MTLBuffer>,
+    _live: Live,
 }
 
-#[native_impl]
 impl Buffer {
-    fn length(args: NativeArguments) {
-        let buffer_instance = args.get_arg(0).unwrap();
-        let buffer = buffer_instance.get_peer::<Buffer>().unwrap();
-        let length = buffer.buffer.length() as i64;
-        args.set_int_return_value(length);
+    fn new(buffer: Id<dyn objc2_metal::MTLBuffer>) -> Self {
+        let _live = BUFFERS.track(buffer.allocatedSize());
+        Self { buffer, _live }
     }
 
-    fn gpu_address(args: NativeArguments) {
-        let buffer_instance = args.get_arg(0).unwrap();
-        let buffer = buffer_instance.get_peer::<Buffer>().unwrap();
-        let addr = buffer.buffer.gpuAddress() as i64;
-        args.set_int_return_value(addr);
+    fn allocated_size(&self) -> usize {
+        self.buffer.allocatedSize()
     }
 
-    fn contents(args: NativeArguments, scope: Scope<'_>) {
-        let buffer_instance = args.get_arg(0).unwrap();
-        let buffer = buffer_instance.get_peer::<Buffer>().unwrap();
-        let length = buffer.buffer.length();
-        let contents_ptr = buffer.buffer.contents().as_ptr() as *const u8;
-
-        // Try to create Uint8List via dart:typed_data
-        // If that fails, fall back to creating a regular List
-        let uint8_list = (|| -> Result<Handle> {
-            let library = scope.library("dart:typed_data")?;
-            let uint8_list_class = scope.get_class(library, "Uint8List")?;
-            let length_handle = scope.new_integer(length as i64)?;
-            let list_instance = scope.new_object(
-                uint8_list_class,
-                scope.null_handle()?,
-                &mut [length_handle.raw()],
-            )?;
-
-            // Copy data into the list using TypedDataView
-            let view = TypedDataView::acquire(list_instance)?;
-            unsafe {
-                let slice = core::slice::from_raw_parts(contents_ptr, length);
-                core::ptr::copy_nonoverlapping(slice.as_ptr(), view.data, length);
-            }
-            Ok(list_instance)
-        })();
-
-        match uint8_list {
-            Ok(list) => args.set_return_value(list),
-            Err(_) => {
-                // Fallback: create a regular List<int>
-                let list_handle = unsafe { crate::dart_api::sys::Dart_NewList(length as isize) };
-                let list = Handle::from_raw(list_handle);
-                if !list.is_null() {
-                    let list_obj = List::new(list).unwrap();
-                    unsafe {
-                        let slice = core::slice::from_raw_parts(contents_ptr, length);
-                        for (i, &byte) in slice.iter().enumerate() {
-                            let byte_handle = scope.new_integer(byte as i64).unwrap();
-                            list_obj.set(i as isize, byte_handle).unwrap();
-                        }
-                    }
-                    args.set_return_value(list);
-                } else {
-                    args.set_return_value(scope.null_handle().unwrap());
-                }
-            }
+    fn typed_contents<'s, T: TypedDataElement>(&self, scope: &Scope<'s>) -> Result<Handle<'s>> {
+        if self.buffer.storageMode() == objc2_metal::MTLStorageMode::Private {
+            return Err(DartError::Api(
+                "a private buffer's contents aren't visible to the CPU".into(),
+            ));
         }
+        let data = self.buffer.contents().as_ptr() as *mut T;
+        let length = self.buffer.length() / std::mem::size_of::<T>();
+        // The list retains the MTLBuffer, so its storage outlives the Dart `Buffer` if need be.
+        let storage = BufferStorage(self.buffer.clone());
+        unsafe { scope.new_external_typed_data(data, length, storage) }
+    }
+}
+
+/// Keeps a buffer's storage alive for the typed lists viewing it.
+struct BufferStorage(#[allow(dead_code)] Id<dyn objc2_metal::MTLBuffer>);
+
+// Safety: the list's finalizer only releases the buffer, which Metal allows on any thread.
+unsafe impl Send for BufferStorage {}
+
+#[native_impl(namespace = "gpu")]
+impl Buffer {
+    fn length(buffer: &Buffer) -> usize {
+        buffer.buffer.length()
     }
 
-    fn set_contents(args: NativeArguments) {
-        let buffer_instance = args.get_arg(0).unwrap();
-        let buffer = buffer_instance.get_peer::<Buffer>().unwrap();
-        let data_handle = args.get_arg(1).unwrap();
+    fn gpu_address(buffer: &Buffer) -> u64 {
+        buffer.buffer.gpuAddress()
+    }
 
+    /// The buffer's storage as a `Uint8List`, without copying. Writes land in the buffer
+    /// directly, and the list keeps the buffer alive.
+    #[dart(signature = "Uint8List contents()")]
+    fn contents<'s>(scope: &Scope<'s>, buffer: &Buffer) -> Result<Handle<'s>> {
+        buffer.typed_contents::<u8>(scope)
+    }
+
+    /// The buffer's storage as a `Float32List`, for writing vertex data in place.
+    #[dart(signature = "Float32List float32Contents()")]
+    fn float32_contents<'s>(scope: &Scope<'s>, buffer: &Buffer) -> Result<Handle<'s>> {
+        buffer.typed_contents::<f32>(scope)
+    }
+
+    fn set_contents(buffer: &Buffer, data: TypedDataView<'_>) {
         let length = buffer.buffer.length();
         let contents_ptr = buffer.buffer.contents().as_ptr() as *mut u8;
 
-        // Use TypedDataView for efficient access
-        let view = TypedDataView::acquire(data_handle).unwrap();
-        let copy_length = core::cmp::min(length, view.len as usize);
+        let bytes = data.as_bytes();
+        let copy_length = core::cmp::min(length, bytes.len());
         unsafe {
-            core::ptr::copy_nonoverlapping(view.data, contents_ptr, copy_length);
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), contents_ptr, copy_length);
         }
-        drop(view);
+        drop(data);
     }
 
-    fn label(args: NativeArguments, scope: Scope<'_>) {
+    fn label(_buffer: &Buffer) -> Option<String> {
         // Label methods may not be available on all MTLBuffer implementations
         // Return null for now - can be implemented if needed
-        args.set_return_value(scope.null_handle().unwrap());
+        None
     }
 
-    fn set_label(_args: NativeArguments, _scope: Scope<'_>) {
+    fn set_label(_buffer: &Buffer, _label: String) {
         // Label methods may not be available on all MTLBuffer implementations
         // No-op for now - can be implemented if needed
     }
 }
 
+/// Compiles the vertex and fragment shaders of `descriptor` to MSL, dumping both with
+/// [`write_shader_dump`].
+fn compile_render_shaders(descriptor: &RenderPipelineDescriptor) -> Result<(String, String)> {
+    let vertex_metal = compile_shader_to_msl("vertex", &descriptor.vertex_shader)?;
+    let fragment_metal = compile_shader_to_msl("fragment", &descriptor.fragment_shader)?;
+
+    write_shader_dump("vertex", &descriptor.vertex_shader, &vertex_metal)?;
+    write_shader_dump("fragment", &descriptor.fragment_shader, &fragment_metal)?;
+    Ok((vertex_metal, fragment_metal))
+}
+
+/// Creates the pipeline state for `descriptor` from compiled shaders, as a Dart
+/// `RenderPipeline` belonging to `gpu_instance`.
+fn new_render_pipeline<'s>(
+    scope: &Scope<'s>,
+    gpu_instance: Handle<'s>,
+    descriptor: &RenderPipelineDescriptor,
+    vertex_metal: &str,
+    fragment_metal: &str,
+) -> Result<Handle<'s>> {
+    let gpu = gpu_instance.peer::<Gpu>()?;
+    let rp_desc = MTL4RenderPipelineDescriptor::new();
+    for i in 0..descriptor.color_attachments.len() {
+        let color_attachment = &descriptor.color_attachments[i];
+        let ca = unsafe { rp_desc.colorAttachments().objectAtIndexedSubscript(i) };
+        ca.setPixelFormat(MTLPixelFormat(color_attachment.pixel_format.0));
+        ca.setWriteMask(MTLColorWriteMask(color_attachment.write_mask.0));
+        ca.setBlendingState(if color_attachment.blend_enabled {
+            MTL4BlendState::Enabled
+        } else {
+            MTL4BlendState::Disabled
+        });
+        ca.setSourceRGBBlendFactor(MTLBlendFactor(
+            color_attachment.source_rgb_blend_factor as usize,
+        ));
+        ca.setDestinationRGBBlendFactor(MTLBlendFactor(
+            color_attachment.destination_rgb_blend_factor as usize,
+        ));
+        ca.setSourceAlphaBlendFactor(MTLBlendFactor(
+            color_attachment.source_alpha_blend_factor as usize,
+        ));
+        ca.setDestinationAlphaBlendFactor(MTLBlendFactor(
+            color_attachment.destination_alpha_blend_factor as usize,
+        ));
+    }
+
+    rp_desc.setInputPrimitiveTopology(MTLPrimitiveTopologyClass(
+        descriptor.primitive_topology as usize,
+    ));
+
+    let vertex_library = new_library(&gpu, vertex_metal)?;
+    let fragment_library = new_library(&gpu, fragment_metal)?;
+    let vfd = objc2_metal::MTL4LibraryFunctionDescriptor::new();
+    vfd.setLibrary(Some(&vertex_library));
+    vfd.setName(Some(&objc2_foundation::NSString::from_str("main0")));
+    rp_desc.setVertexFunctionDescriptor(Some(&*vfd));
+
+    let ffd = objc2_metal::MTL4LibraryFunctionDescriptor::new();
+    ffd.setLibrary(Some(&fragment_library));
+    ffd.setName(Some(&objc2_foundation::NSString::from_str("main0")));
+    rp_desc.setFragmentFunctionDescriptor(Some(&*ffd));
+
+    let render_pipeline_state = gpu
+        .compiler
+        .newRenderPipelineStateWithDescriptor_compilerTaskOptions_error(&rp_desc, None)
+        .map_err(|error| {
+            DartError::Api(format!(
+                "failed to create render pipeline state: {}",
+                error.localizedDescription()
+            ))
+        })?;
+
+    let class_type = scope.class(LIBRARY, "RenderPipeline")?;
+    let class_instance = scope.new_object(class_type, scope.null_handle()?, &mut [])?;
+    class_instance.set_peer(Box::new(RenderPipeline {
+        render_pipeline_state,
+        _live: RENDER_PIPELINES.track(0),
+    }))?;
+    class_instance.set_field(scope.new_string("gpu")?, &gpu_instance);
+    Ok(class_instance)
+}
+
+/// Compiles a Slang shader stage to SPIR-V with `slangc` and cross-compiles it to MSL
+/// with `spirv-cross`.
+fn compile_shader_to_msl(stage: &str, shader: &ShaderLibrary) -> Result<String> {
+    let spirv = Command::new("slangc")
+        .arg("-stage")
+        .arg(stage)
+        .arg("-target")
+        .arg("spirv")
+        .arg("-entry")
+        .arg(shader.entry_point.as_str())
+        .arg(shader.path.as_str())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|error| DartError::Api(format!("failed to run slangc: {}", error)))?;
+    if !spirv.status.success() {
+        return Err(DartError::Api(format!(
+            "slangc failed to compile {} shader {}: {}",
+            stage,
+            shader.path,
+            String::from_utf8_lossy(&spirv.stderr).trim()
+        )));
+    }
+
+    let mut child = Command::new("spirv-cross")
+        .arg("-")
+        .arg("--msl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| DartError::Api(format!("failed to run spirv-cross: {}", error)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(&spirv.stdout)
+            .and_then(|_| stdin.flush())
+            .map_err(|error| {
+                DartError::Api(format!("failed to write to spirv-cross: {}", error))
+            })?;
+    }
+    let msl = child
+        .wait_with_output()
+        .map_err(|error| DartError::Api(format!("failed to run spirv-cross: {}", error)))?;
+    if !msl.status.success() {
+        return Err(DartError::Api(format!(
+            "spirv-cross failed on {} shader {}: {}",
+            stage,
+            shader.path,
+            String::from_utf8_lossy(&msl.stderr).trim()
+        )));
+    }
+
+    String::from_utf8(msl.stdout)
+        .map_err(|_| DartError::Api("spirv-cross produced invalid UTF-8".to_string()))
+}
+
+/// Writes the MSL of `shader` to `target/shaders/<file>.<entry point>.<stage>.metal`.
+/// Pipelines compile concurrently, so each dump goes through its own temporary file and
+/// replaces the previous one whole.
+fn write_shader_dump(stage: &str, shader: &ShaderLibrary, source: &str) -> Result<()> {
+    static NEXT_DUMP: AtomicUsize = AtomicUsize::new(0);
+
+    let file = std::path::Path::new(&shader.path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let path = format!(
+        "target/shaders/{}.{}.{}.metal",
+        file, shader.entry_point, stage
+    );
+    let temporary = format!(
+        "{}.{}-{}",
+        path,
+        std::process::id(),
+        NEXT_DUMP.fetch_add(1, Ordering::Relaxed)
+    );
+    std::fs::write(&temporary, source)
+        .and_then(|()| std::fs::rename(&temporary, &path))
+        .map_err(|error| DartError::Api(format!("failed to write {}: {}", path, error)))
+}
+
+fn new_library(gpu: &Gpu, source: &str) -> Result<Id<dyn MTLLibrary>> {
+    gpu.device
+        .newLibraryWithSource_options_error(&objc2_foundation::NSString::from_str(source), None)
+        .map_err(|error| {
+            DartError::Api(format!(
+                "failed to compile Metal library: {}",
+                error.localizedDescription()
+            ))
+        })
+}
+
 fn to_mtl4_buffer_range(range: &BufferRangeData) -> MTL4BufferRange {
     let buffer_address = if range.gpu_address < 0 {
         0
@@ -1444,6 +1440,14 @@ struct BufferRangeData {
     length: i64,
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AccelerationStructureSizesData {
+    acceleration_structure_size: u64,
+    build_scratch_buffer_size: u64,
+    refit_scratch_buffer_size: u64,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(tag = "type", rename_all = "camelCase")]
 enum AccelerationStructureDescriptorData {
@@ -1485,8 +1489,15 @@ struct ComputePipelineDescriptor {
     compute_shader: ShaderLibrary,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-struct PrimitiveTopology(usize);
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+#[repr(usize)]
+enum PrimitiveTopology {
+    Unspecified = 0,
+    Point = 1,
+    Line = 2,
+    Triangle = 3,
+}
 
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -1512,11 +1523,41 @@ struct RenderPipelineDescriptorColorAttachment {
 #[derive(Deserialize, Serialize, Debug)]
 struct ColorWriteMask(usize);
 
-#[derive(Deserialize, Serialize, Debug)]
-struct BlendOp(usize);
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+#[repr(usize)]
+enum BlendOp {
+    Add = 0,
+    Subtract = 1,
+    ReverseSubtract = 2,
+    Min = 3,
+    Max = 4,
+}
 
-#[derive(Deserialize, Serialize, Debug)]
-struct BlendFactor(usize);
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+#[repr(usize)]
+enum BlendFactor {
+    Zero = 0,
+    One = 1,
+    SourceColor = 2,
+    OneMinusSourceColor = 3,
+    SourceAlpha = 4,
+    OneMinusSourceAlpha = 5,
+    DestinationColor = 6,
+    OneMinusDestinationColor = 7,
+    DestinationAlpha = 8,
+    OneMinusDestinationAlpha = 9,
+    SourceAlphaSaturated = 10,
+    BlendColor = 11,
+    OneMinusBlendColor = 12,
+    BlendAlpha = 13,
+    OneMinusBlendAlpha = 14,
+    Source1Color = 15,
+    OneMinusSource1Color = 16,
+    Source1Alpha = 17,
+    OneMinusSource1Alpha = 18,
+}
 
 #[derive(Deserialize, Serialize, Debug)]
 struct PixelFormat(usize);
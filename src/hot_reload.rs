@@ -0,0 +1,182 @@
+//! Hot reload from inside the engine: a thread polls the app's sources and, once edits
+//! settle, reloads every isolate through the VM service. Reloads the VM rejects can fall
+//! back to a hot restart.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde_json::json;
+
+use crate::engine;
+use crate::service::{self, ServiceError};
+
+/// How often the sources are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long the sources must stay unchanged before reloading, so saving several files
+/// at once reloads once.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Watches a source directory and hot-reloads the isolates when it changes. Dropping it
+/// stops the watcher thread.
+pub struct HotReloader {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HotReloader {
+    /// Watches `sources`. With `restart`, a rejected reload asks the engine for a hot
+    /// restart.
+    pub fn spawn(sources: PathBuf, restart: bool) -> std::io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::Builder::new().name("hot-reload".into()).spawn({
+            let stop = stop.clone();
+            move || watch(&sources, restart, &stop)
+        })?;
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Asks the watcher to stop without waiting for it, e.g. before shutting down an
+    /// isolate that an in-flight reload is waiting on.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for HotReloader {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn watch(sources: &Path, restart: bool, stop: &AtomicBool) {
+    println!("Watching: {}", sources.display());
+    let mut stamps = scan(sources);
+    let mut changed_at: Option<Instant> = None;
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+        let current = scan(sources);
+        if current != stamps {
+            for path in changed_paths(&stamps, &current) {
+                println!("File changed: {}", path.display());
+            }
+            stamps = current;
+            changed_at = Some(Instant::now());
+        } else if changed_at.is_some_and(|at| at.elapsed() >= DEBOUNCE) {
+            changed_at = None;
+            if !reload() && restart {
+                engine::request_restart();
+            }
+        }
+    }
+}
+
+/// The modification times of the Dart sources under `dir`.
+fn scan(dir: &Path) -> BTreeMap<PathBuf, SystemTime> {
+    let mut stamps = BTreeMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                // Skips tool output such as `.dart_tool`.
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    pending.push(path);
+                }
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "dart")
+            {
+                if let Ok(modified) = metadata.modified() {
+                    stamps.insert(path, modified);
+                }
+            }
+        }
+    }
+    stamps
+}
+
+/// Paths added, removed or modified between two scans.
+fn changed_paths<'a>(
+    before: &'a BTreeMap<PathBuf, SystemTime>,
+    after: &'a BTreeMap<PathBuf, SystemTime>,
+) -> impl Iterator<Item = &'a PathBuf> {
+    let removed = before.keys().filter(|path| !after.contains_key(*path));
+    let changed = after
+        .iter()
+        .filter(|(path, modified)| before.get(*path) != Some(modified))
+        .map(|(path, _)| path);
+    removed.chain(changed)
+}
+
+/// Returns false if an isolate rejected the reload.
+fn reload() -> bool {
+    println!("Reloading...");
+    let started = Instant::now();
+    match reload_isolates() {
+        Ok((reloaded, rejected)) => {
+            if reloaded > 0 {
+                println!(
+                    "Reloaded {} isolate(s) in {} ms",
+                    reloaded,
+                    started.elapsed().as_millis()
+                );
+            }
+            !rejected
+        }
+        Err(error) => {
+            eprintln!("Hot reload failed: {}", error);
+            true
+        }
+    }
+}
+
+/// Reloads the sources of every isolate, then re-resolves their natives. Compile errors
+/// and changes a reload can't apply cancel the reload of an isolate and are printed.
+/// Returns how many isolates reloaded, and whether any rejected the reload.
+fn reload_isolates() -> Result<(usize, bool), ServiceError> {
+    let vm = service::call("getVM", json!({}))?;
+    let mut reloaded = 0;
+    let mut rejected = false;
+    for isolate in vm["isolates"].as_array().into_iter().flatten() {
+        let Some(id) = isolate["id"].as_str() else {
+            continue;
+        };
+        let name = isolate["name"].as_str().unwrap_or(id);
+        let report = service::call("reloadSources", json!({ "isolateId": id }))?;
+        if report["success"].as_bool() == Some(true) {
+            // Let the engine install its native resolver on libraries the reload added.
+            if let Err(error) =
+                service::call("ext.bigfish.reloadNatives", json!({ "isolateId": id }))
+            {
+                eprintln!("Failed to reload natives of {}: {}", name, error);
+            }
+            reloaded += 1;
+            continue;
+        }
+        eprintln!("Failed to reload isolate {}", name);
+        rejected = true;
+        for notice in report["notices"].as_array().into_iter().flatten() {
+            if let Some(message) = notice["message"].as_str() {
+                eprintln!("{}", message);
+            }
+        }
+    }
+    Ok((reloaded, rejected))
+}
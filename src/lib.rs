@@ -0,0 +1,12 @@
+pub mod bindings;
+pub mod dart_api;
+mod engine;
+pub mod gpu;
+mod hot_reload;
+pub mod registry;
+pub mod service;
+pub mod snapshot;
+pub mod window;
+pub mod worker;
+
+pub use engine::{Engine, EngineBuilder};
@@ -1,87 +1,97 @@
-use std::process::{Child, Command, Stdio};
-use std::sync::{Arc, Mutex};
-
 use clap::Parser;
 
-mod dart_api;
-mod gpu;
-mod window;
-use dart_api::{Runtime, RuntimeConfig};
-
-use crate::dart_api::native_resolver;
+use bigfish::{bindings, snapshot, Engine};
 
 #[derive(clap::Parser)]
 struct Args {
     #[clap(long, default_value = if cfg!(debug_assertions) { "true" } else { "false" })]
     hmr: bool,
+    /// Don't hot-restart the app when a hot reload is rejected.
+    #[clap(long)]
+    no_hot_restart: bool,
+    /// Resolve every native declared in the loaded native libraries before running `main`,
+    /// and exit listing any that are unregistered or take the wrong number of arguments.
+    #[clap(long, conflicts_with = "snapshot")]
+    check_natives: bool,
+    /// The Dart script to run.
+    #[clap(long, default_value = "./app/lib/main.dart")]
+    script: String,
+    /// Run a kernel snapshot built by the `snapshot` subcommand instead of the script.
+    #[clap(long, conflicts_with = "script")]
+    snapshot: Option<String>,
+    /// The package config the script's `package:` imports resolve against.
+    #[clap(long, default_value = "./app/.dart_tool/package_config.json")]
+    packages: String,
+    /// The library declaring the entry function, by default the script's own.
+    #[clap(long)]
+    library: Option<String>,
+    /// The function to run. It's passed the trailing arguments if it takes a
+    /// `List<String>`.
+    #[clap(long, default_value = "main")]
+    entry: String,
+    /// Defines `NAME=VALUE` for `String.fromEnvironment` and friends.
+    #[clap(short = 'D', long = "define", value_name = "NAME=VALUE", value_parser = parse_define)]
+    defines: Vec<(String, String)>,
+    /// Arguments for the entry function, after `--`.
+    #[clap(last = true)]
+    args: Vec<String>,
+    #[clap(subcommand)]
+    command: Option<Subcommand>,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Generate the Dart bindings for the Rust natives (`app/lib/*.g.dart`).
+    Bindings(bindings::BindingsArgs),
+    /// Compile an app directory to a kernel snapshot for `--snapshot`.
+    Snapshot(snapshot::SnapshotArgs),
 }
 
 fn main() {
     let args = Args::parse();
 
-    // If we spawn the Dart hot-reload watcher, ensure Ctrl+C always kills it.
-    // Without this, interrupting the Rust process can leave the Dart process running.
-    let hot_reload_proc: Arc<Mutex<Option<Child>>> = Arc::new(Mutex::new(None));
-    {
-        let hot_reload_proc = Arc::clone(&hot_reload_proc);
-        ctrlc::set_handler(move || {
-            eprintln!("\nInterrupted. Shutting down...");
-            if let Ok(mut guard) = hot_reload_proc.lock() {
-                if let Some(mut child) = guard.take() {
-                    let _ = child.kill();
-                }
-            }
-            std::process::exit(130);
-        })
-        .expect("failed to set Ctrl+C handler");
+    if let Some(command) = args.command {
+        let result = match command {
+            Subcommand::Bindings(bindings_args) => bindings::run(bindings_args),
+            Subcommand::Snapshot(snapshot_args) => snapshot::run(snapshot_args),
+        };
+        if let Err(error) = result {
+            eprintln!("{:#}", error);
+            std::process::exit(1);
+        }
+        return;
     }
 
-    let engine = Runtime::initialize(RuntimeConfig {
-        service_port: 5858,
-        start_service_isolate: args.hmr,
-    })
-    .unwrap();
-
-    let mut isolate = engine
-        .load_script(
-            c"./app/lib/main.dart",
-            c"./app/.dart_tool/package_config.json",
-        )
-        .unwrap();
-
-    // Start the Dart hot-reload watcher CLI (best-effort).
-    // Requested command: `dart run cli/bin/cli.dart app/lib`
-    if args.hmr {
-        let child = Command::new("dart")
-            .args(["run", "cli/bin/cli.dart", "app/lib"])
-            .stdin(Stdio::null())
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn()
-            .expect("failed to spawn hot-reload watcher");
-
-        *hot_reload_proc.lock().expect("poisoned watcher lock") = Some(child);
+    let mut builder = Engine::builder()
+        // Hot reload works on sources, so a snapshot runs without it.
+        .hmr(args.hmr && args.snapshot.is_none())
+        .hot_restart(!args.no_hot_restart)
+        .check_natives(args.check_natives)
+        .script(args.script)
+        .package_config(args.packages)
+        .entry_point(args.entry)
+        .args(args.args);
+    if let Some(snapshot) = args.snapshot {
+        builder = builder.snapshot(snapshot);
     }
-
-    {
-        let mut scope = isolate.enter();
-        let library = scope.library("package:app/native.dart").unwrap();
-        scope.set_native_resolver(library, Some(native_resolver));
-
-        let root_library = scope.library("package:app/main.dart").unwrap();
-        scope.invoke(root_library, "main", &mut []).unwrap();
+    if let Some(library) = args.library {
+        builder = builder.entry_library(library);
+    }
+    for (name, value) in args.defines {
+        builder = builder.define(name, value);
+    }
+    let result = builder.build().and_then(|mut engine| engine.run());
+    // The engine has shut down by now, whether `run` failed or not.
+    if let Err(error) = result {
+        eprintln!("{:#}", error);
+        std::process::exit(1);
     }
-
     println!("Exiting...");
+}
 
-    // Clean up watcher when we exit.
-    if let Some(mut child) = hot_reload_proc
-        .lock()
-        .expect("poisoned watcher lock")
-        .take()
-    {
-        let _ = child.kill();
-        let _ = child.wait();
-    }
-    std::process::exit(0);
+fn parse_define(define: &str) -> Result<(String, String), String> {
+    define
+        .split_once('=')
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected NAME=VALUE, got `{}`", define))
 }
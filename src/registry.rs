@@ -0,0 +1,112 @@
+//! Native objects kept alive across a hot restart.
+//!
+//! The app retains an object under a name, e.g. `Window.retained('main', ...)`. Before
+//! the engine shuts the old isolate down, [`detach_all`] takes each retained peer out of
+//! its Dart object, so the isolate's finalizers leave it alone. The new isolate then
+//! [`attach`]es the peer to a fresh Dart object of the same name and type.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::dart_api::{DartError, Handle, NativePeer, PersistentHandle, Result, Scope};
+
+/// A peer that can be carried over to the next isolate.
+pub trait Retain: NativePeer {
+    /// Lets go of everything that belongs to the isolate being shut down, such as
+    /// persistent handles, before the peer is moved out of it.
+    fn detach(&mut self) {}
+}
+
+/// An object retained by the running isolate.
+struct Retained {
+    object: PersistentHandle,
+    detach: fn(Handle<'_>) -> Result<Detached>,
+}
+
+/// A peer waiting for the next isolate to attach it.
+struct Detached {
+    type_name: &'static str,
+    peer: Box<dyn Any>,
+}
+
+thread_local! {
+    static RETAINED: RefCell<HashMap<String, Retained>> = RefCell::new(HashMap::new());
+    static DETACHED: RefCell<HashMap<String, Detached>> = RefCell::new(HashMap::new());
+}
+
+/// Keeps the peer of `instance` across hot restarts under `name`, replacing whatever was
+/// retained under it before.
+pub fn retain<T: Retain>(instance: Handle<'_>, name: &str) -> Result<()> {
+    // Fail now rather than at restart if the object isn't a `T`.
+    instance.peer::<T>()?;
+    let retained = Retained {
+        object: PersistentHandle::new(instance)?,
+        detach: detach::<T>,
+    };
+    RETAINED.with_borrow_mut(|objects| objects.insert(name.to_string(), retained));
+    Ok(())
+}
+
+/// Attaches the peer retained under `name` by the previous isolate to `instance`.
+/// Returns false if there is none, e.g. on the first run.
+pub fn attach<T: Retain>(instance: Handle<'_>, name: &str) -> Result<bool> {
+    let Some(detached) = DETACHED.with_borrow_mut(|objects| objects.remove(name)) else {
+        return Ok(false);
+    };
+    let peer = match detached.peer.downcast::<T>() {
+        Ok(peer) => peer,
+        Err(peer) => {
+            let type_name = detached.type_name;
+            DETACHED.with_borrow_mut(|objects| {
+                objects.insert(name.to_string(), Detached { type_name, peer })
+            });
+            return Err(DartError::PeerTypeMismatch {
+                expected: std::any::type_name::<T>(),
+                found: type_name,
+            });
+        }
+    };
+    let external_size = peer.external_size();
+    instance.set_peer_with_size(peer, external_size)?;
+    retain::<T>(instance, name)?;
+    Ok(true)
+}
+
+fn detach<T: Retain>(instance: Handle<'_>) -> Result<Detached> {
+    let mut peer = instance.take_peer::<T>()?;
+    peer.detach();
+    Ok(Detached {
+        type_name: std::any::type_name::<T>(),
+        peer,
+    })
+}
+
+/// Moves the retained peers out of the isolate of `scope`, which is about to shut down.
+/// Peers the isolate didn't attach since the last restart are dropped.
+pub fn detach_all(scope: &Scope<'_>) {
+    let retained = RETAINED.take();
+    let detached = retained
+        .into_iter()
+        .filter_map(|(name, retained)| {
+            let detached = retained
+                .object
+                .get(scope)
+                .and_then(|object| (retained.detach)(object));
+            match detached {
+                Ok(detached) => Some((name, detached)),
+                Err(error) => {
+                    eprintln!("Failed to retain `{}`: {}", name, error.pretty());
+                    None
+                }
+            }
+        })
+        .collect();
+    DETACHED.set(detached);
+}
+
+/// Drops everything retained, with the isolate of the retained objects entered.
+pub fn clear() {
+    RETAINED.take();
+    DETACHED.take();
+}
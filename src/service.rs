@@ -0,0 +1,317 @@
+//! VM service protocol extensions, so DevTools and scripts can query the engine over the
+//! VM service websocket.
+//!
+//! Extensions are either registered at runtime with [`register_isolate_extension`] and
+//! [`register_root_extension`], or declared with `inventory::submit!` of a
+//! [`ServiceExtension`], which [`register_extensions`] picks up. Isolate extensions run
+//! on the isolate's thread with a scope of their own; root extensions run on the service
+//! isolate's thread without one.
+//!
+//! [`call`] goes the other way, invoking service methods from inside the engine.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::sync::Once;
+
+use serde_json::{json, Value};
+
+use crate::dart_api::{self, sys, DartError, Isolate, NativeFunction, NativeLibrary, Scope};
+
+/// The error code of failed extension calls, the first the protocol reserves for them.
+const EXTENSION_ERROR: i64 = -32000;
+
+/// The result object of an extension call. Objects without a `type` are sent as
+/// `"type": "Success"`.
+pub type ServiceResult = std::result::Result<Value, ServiceError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+    #[error("missing parameter `{0}`")]
+    MissingParam(&'static str),
+    #[error("invalid parameter `{name}`: {reason}")]
+    InvalidParam { name: &'static str, reason: String },
+    #[error(transparent)]
+    Dart(#[from] DartError),
+    /// A method invoked with [`call`] failed.
+    #[error("`{method}` failed: {message}")]
+    Rpc { method: String, message: String },
+}
+
+/// The parameters of a service request, including `isolateId` for isolate extensions.
+pub struct ServiceParams {
+    params: Vec<(String, String)>,
+}
+
+impl ServiceParams {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn require(&self, name: &'static str) -> Result<&str, ServiceError> {
+        self.get(name).ok_or(ServiceError::MissingParam(name))
+    }
+
+    unsafe fn from_raw(keys: *mut *const c_char, values: *mut *const c_char, len: isize) -> Self {
+        let params = (0..len.max(0) as usize)
+            .map(|i| {
+                let key = CStr::from_ptr(*keys.add(i));
+                let value = CStr::from_ptr(*values.add(i));
+                (
+                    key.to_string_lossy().into_owned(),
+                    value.to_string_lossy().into_owned(),
+                )
+            })
+            .collect();
+        Self { params }
+    }
+}
+
+type IsolateHandler = Box<dyn Fn(&Scope<'_>, &ServiceParams) -> ServiceResult + Send + Sync>;
+type RootHandler = Box<dyn Fn(&ServiceParams) -> ServiceResult + Send + Sync>;
+
+enum Handler {
+    Isolate(fn(&Scope<'_>, &ServiceParams) -> ServiceResult),
+    Root(fn(&ServiceParams) -> ServiceResult),
+}
+
+/// A service extension installed by [`register_extensions`]. Register more with
+/// `inventory::submit!`.
+pub struct ServiceExtension {
+    method: &'static str,
+    handler: Handler,
+}
+
+impl ServiceExtension {
+    /// An extension of every isolate the engine runs. `method` must start with `ext.`.
+    pub const fn isolate(
+        method: &'static str,
+        handler: fn(&Scope<'_>, &ServiceParams) -> ServiceResult,
+    ) -> Self {
+        Self {
+            method,
+            handler: Handler::Isolate(handler),
+        }
+    }
+
+    /// An extension of the VM, which needs no isolate to answer.
+    pub const fn root(method: &'static str, handler: fn(&ServiceParams) -> ServiceResult) -> Self {
+        Self {
+            method,
+            handler: Handler::Root(handler),
+        }
+    }
+
+    pub fn method(&self) -> &'static str {
+        self.method
+    }
+}
+
+inventory::collect!(ServiceExtension);
+
+/// Installs the declared extensions. The VM keeps them for every isolate, including
+/// ones started later, so only the first call does anything.
+pub fn register_extensions() {
+    static REGISTERED: Once = Once::new();
+    REGISTERED.call_once(|| {
+        for extension in inventory::iter::<ServiceExtension>() {
+            match extension.handler {
+                Handler::Root(handler) => register_root_extension(extension.method, handler),
+                Handler::Isolate(handler) => register_isolate_extension(extension.method, handler),
+            }
+        }
+    });
+}
+
+/// Answers `method` on every isolate with `handler`.
+pub fn register_isolate_extension(
+    method: &str,
+    handler: impl Fn(&Scope<'_>, &ServiceParams) -> ServiceResult + Send + Sync + 'static,
+) {
+    let method = CString::new(method).expect("method contains no NUL");
+    // The VM keeps the callback for its lifetime, and never tells us when it's done with
+    // it.
+    let handler: *mut IsolateHandler = Box::into_raw(Box::new(Box::new(handler)));
+    unsafe {
+        sys::Dart_RegisterIsolateServiceRequestCallback(
+            method.as_ptr(),
+            Some(isolate_callback),
+            handler as *mut c_void,
+        )
+    };
+}
+
+/// Answers `method` on the VM with `handler`.
+pub fn register_root_extension(
+    method: &str,
+    handler: impl Fn(&ServiceParams) -> ServiceResult + Send + Sync + 'static,
+) {
+    let method = CString::new(method).expect("method contains no NUL");
+    let handler: *mut RootHandler = Box::into_raw(Box::new(Box::new(handler)));
+    unsafe {
+        sys::Dart_RegisterRootServiceRequestCallback(
+            method.as_ptr(),
+            Some(root_callback),
+            handler as *mut c_void,
+        )
+    };
+}
+
+extern "C" {
+    fn strdup(s: *const c_char) -> *mut c_char;
+    fn free(ptr: *mut c_void);
+}
+
+/// Invokes the VM service `method` in-process and waits for its result. Needs the
+/// service isolate, and must not be called on the thread of an isolate the method
+/// targets, which has to handle the request.
+pub fn call(method: &str, params: Value) -> ServiceResult {
+    let failed = |message: String| ServiceError::Rpc {
+        method: method.to_string(),
+        message,
+    };
+    let mut request = json!({ "jsonrpc": "2.0", "id": "0", "method": method, "params": params })
+        .to_string()
+        .into_bytes();
+    let mut response: *mut u8 = std::ptr::null_mut();
+    let mut response_len = 0;
+    let mut error: *mut c_char = std::ptr::null_mut();
+    let ok = unsafe {
+        sys::Dart_InvokeVMServiceMethod(
+            request.as_mut_ptr(),
+            request.len() as isize,
+            &mut response,
+            &mut response_len,
+            &mut error,
+        )
+    };
+    // Both are ours to free.
+    let response = unsafe { take_malloced(response, response_len.max(0) as usize) };
+    if !error.is_null() {
+        let message = unsafe { CStr::from_ptr(error) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { free(error as *mut c_void) };
+        return Err(failed(message));
+    }
+    if !ok {
+        return Err(failed("the VM service didn't answer".into()));
+    }
+    let mut response: Value = serde_json::from_slice(&response.unwrap_or_default())
+        .map_err(|error| failed(format!("invalid response: {}", error)))?;
+    if let Some(error) = response.get("error") {
+        let message = error["data"]["details"]
+            .as_str()
+            .or(error["message"].as_str())
+            .unwrap_or("unknown error");
+        return Err(failed(message.to_string()));
+    }
+    Ok(response["result"].take())
+}
+
+/// Copies out and frees a buffer the VM allocated with `malloc`.
+unsafe fn take_malloced(data: *mut u8, len: usize) -> Option<Vec<u8>> {
+    if data.is_null() {
+        return None;
+    }
+    let bytes = std::slice::from_raw_parts(data, len).to_vec();
+    free(data as *mut c_void);
+    Some(bytes)
+}
+
+unsafe extern "C" fn isolate_callback(
+    _method: *const c_char,
+    param_keys: *mut *const c_char,
+    param_values: *mut *const c_char,
+    num_params: isize,
+    user_data: *mut c_void,
+    json_object: *mut *const c_char,
+) -> bool {
+    let handler = &*(user_data as *const IsolateHandler);
+    let params = ServiceParams::from_raw(param_keys, param_values, num_params);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        // The request is handled between messages, outside any scope.
+        Isolate::current()?.with_scope(|scope| handler(scope, &params))
+    }))
+    .unwrap_or_else(|payload| Err(DartError::from_panic(payload).into()));
+    respond(result, json_object)
+}
+
+unsafe extern "C" fn root_callback(
+    _method: *const c_char,
+    param_keys: *mut *const c_char,
+    param_values: *mut *const c_char,
+    num_params: isize,
+    user_data: *mut c_void,
+    json_object: *mut *const c_char,
+) -> bool {
+    let handler = &*(user_data as *const RootHandler);
+    let params = ServiceParams::from_raw(param_keys, param_values, num_params);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| handler(&params)))
+        .unwrap_or_else(|payload| Err(DartError::from_panic(payload).into()));
+    respond(result, json_object)
+}
+
+/// Hands the VM the result object, or the JSON-RPC error object of a failed call.
+unsafe fn respond(result: ServiceResult, json_object: *mut *const c_char) -> bool {
+    let (success, response) = match result {
+        Ok(Value::Object(mut object)) => {
+            object
+                .entry("type")
+                .or_insert_with(|| Value::from("Success"));
+            (true, Value::Object(object))
+        }
+        Ok(value) => (true, json!({ "type": "Success", "value": value })),
+        Err(error) => (
+            false,
+            json!({
+                "code": EXTENSION_ERROR,
+                "message": error.to_string(),
+                "data": { "details": format!("{:?}", error) },
+            }),
+        ),
+    };
+    // The VM releases the response with `free`.
+    let response = CString::new(response.to_string()).expect("JSON contains no NUL");
+    *json_object = strdup(response.as_ptr());
+    success
+}
+
+/// Re-resolves the natives of every loaded native library. Hot reload calls it after
+/// `reloadSources`, so libraries the reload brought in get a resolver and cached
+/// classes are looked up again.
+fn reload_natives(scope: &Scope<'_>, _params: &ServiceParams) -> ServiceResult {
+    dart_api::clear_class_cache();
+    let libraries = scope.install_native_resolvers();
+    Ok(json!({ "libraries": libraries }))
+}
+
+/// The native libraries and functions linked into the engine.
+fn natives(_params: &ServiceParams) -> ServiceResult {
+    let libraries: Vec<Value> = inventory::iter::<NativeLibrary>()
+        .map(|library| json!({ "uri": library.uri(), "namespace": library.namespace() }))
+        .collect();
+    let functions: Vec<Value> = inventory::iter::<NativeFunction>()
+        .map(|function| {
+            let signature = function.signature();
+            json!({
+                "name": function.name(),
+                "namespace": function.namespace(),
+                "arity": function.arity(),
+                "class": signature.and_then(|signature| signature.class),
+                "dartName": signature.map(|signature| signature.dart_name),
+            })
+        })
+        .collect();
+    Ok(json!({ "type": "Natives", "libraries": libraries, "functions": functions }))
+}
+
+inventory::submit! {
+    ServiceExtension::isolate("ext.bigfish.reloadNatives", reload_natives)
+}
+
+inventory::submit! {
+    ServiceExtension::root("ext.bigfish.natives", natives)
+}
@@ -0,0 +1,85 @@
+//! Precompiled apps, which start without running the Dart frontend over their sources.
+//!
+//! dart_dll embeds the JIT VM, which reads kernel binaries (`.dill`) in place of a
+//! source script. AOT and app-JIT snapshots need a VM built for them, so they are
+//! recognized only to be turned down with a clear error.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context};
+
+/// The magic number kernel binaries start with.
+const KERNEL_MAGIC: [u8; 4] = [0x90, 0xab, 0xcd, 0xef];
+
+/// Leading bytes of the snapshots the standalone VM or `gen_snapshot` produce: app
+/// snapshots, bare VM snapshots and AOT ELF and Mach-O libraries.
+const APP_SNAPSHOT_MAGICS: &[[u8; 4]] = &[
+    [0xdc, 0xdc, 0xf6, 0xf6],
+    [0xf5, 0xf5, 0xdc, 0xdc],
+    [0x7f, b'E', b'L', b'F'],
+    [0xcf, 0xfa, 0xed, 0xfe],
+];
+
+#[derive(clap::Args)]
+pub struct SnapshotArgs {
+    /// The app directory, holding the script and `.dart_tool/package_config.json`.
+    #[clap(default_value = "app")]
+    app: PathBuf,
+    /// The script to compile, relative to the app directory.
+    #[clap(long, default_value = "lib/main.dart")]
+    script: PathBuf,
+    /// Where to write the kernel snapshot.
+    #[clap(long, default_value = "build/app.dill")]
+    out: PathBuf,
+    /// Defines `NAME=VALUE` for `String.fromEnvironment` and friends at compile time.
+    #[clap(short = 'D', long = "define", value_name = "NAME=VALUE")]
+    defines: Vec<String>,
+}
+
+/// Compiles the app to a kernel snapshot with `dart compile kernel`.
+pub fn run(args: SnapshotArgs) -> anyhow::Result<()> {
+    if let Some(parent) = args
+        .out
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let status = Command::new("dart")
+        .args(["compile", "kernel"])
+        .arg(args.app.join(&args.script))
+        .arg("--packages")
+        .arg(args.app.join(".dart_tool/package_config.json"))
+        .arg("--output")
+        .arg(&args.out)
+        .args(args.defines.iter().map(|define| format!("-D{}", define)))
+        .status()
+        .context("failed to run `dart compile kernel`")?;
+    if !status.success() {
+        bail!("`dart compile kernel` failed ({})", status);
+    }
+    println!("Wrote {}", args.out.display());
+    Ok(())
+}
+
+/// Checks that `path` holds a snapshot this VM can start from.
+pub fn check(path: &Path) -> anyhow::Result<()> {
+    let mut magic = [0; 4];
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    std::io::Read::read_exact(&mut file, &mut magic)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    if magic == KERNEL_MAGIC {
+        return Ok(());
+    }
+    if APP_SNAPSHOT_MAGICS.contains(&magic) {
+        bail!(
+            "{} is an AOT or app-JIT snapshot, which dart_dll's JIT VM can't load; build a \
+             kernel snapshot with `bigfish snapshot`",
+            path.display()
+        );
+    }
+    bail!("{} is not a kernel snapshot", path.display())
+}
@@ -1,8 +1,38 @@
-use bigfish_macros::native_func;
+use bigfish_macros::{native_func, NativePeer};
 
-use crate::dart_api::{Isolate, NativeArguments, PersistentHandle};
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
 
+use serde_json::json;
+
+use crate::dart_api::{
+    DartClosure, DartError, Handle, NativeLibrary, PersistentHandle, Result, Scope,
+};
+use crate::registry::{self, Retain};
+use crate::service::{ServiceExtension, ServiceParams, ServiceResult};
+
+/// The Dart library declaring `Window`, whose natives are in the `window` namespace.
+const LIBRARY: &str = "package:app/window.dart";
+
+inventory::submit! {
+    NativeLibrary::new(LIBRARY, "window")
+}
+
+#[derive(NativePeer)]
+#[native_peer(library = LIBRARY)]
 pub struct Window {
+    /// The SDL window, until it is closed.
+    surface: Option<Surface>,
+    update_callback: Option<PersistentHandle>,
+    present_callback: Option<PersistentHandle>,
+    clock: chron::Clock,
+    timing: FrameTiming,
+}
+
+/// The SDL window and the Metal layer drawn to it. SDL only lets the main thread close
+/// them, so [`Window::close`] does when the window leaves [`WINDOWS`], rather than the
+/// finalizer, which may run on any thread.
+struct Surface {
     ctx: sdl3::Sdl,
     #[allow(dead_code)]
     window: sdl3::video::Window,
@@ -10,31 +40,69 @@ pub struct Window {
     metal_view: sdl3::sys::metal::SDL_MetalView,
     #[cfg(target_os = "macos")]
     metal_layer: objc2::rc::Retained<objc2_quartz_core::CAMetalLayer>,
-    update_callback: Option<PersistentHandle>,
-    present_callback: Option<PersistentHandle>,
-    clock: chron::Clock,
 }
 
-// Safety: SDL windows are not thread-safe, but we protect all access with a Mutex.
-// This ensures only one thread can access the window at a time, making it safe to Send/Sync.
+/// How long a window's callbacks take, reported by `ext.bigfish.frameTiming`.
+#[derive(Default)]
+struct FrameTiming {
+    updates: u64,
+    presents: u64,
+    last_update: Duration,
+    last_present: Duration,
+    max_update: Duration,
+    max_present: Duration,
+    /// Time between the last two presents.
+    frame_interval: Duration,
+    last_present_at: Option<Instant>,
+}
+
+impl FrameTiming {
+    fn record_update(&mut self, elapsed: Duration) {
+        self.updates += 1;
+        self.last_update = elapsed;
+        self.max_update = self.max_update.max(elapsed);
+    }
+
+    fn record_present(&mut self, started: Instant, elapsed: Duration) {
+        self.presents += 1;
+        self.last_present = elapsed;
+        self.max_present = self.max_present.max(elapsed);
+        if let Some(last_present_at) = self.last_present_at.replace(started) {
+            self.frame_interval = started - last_present_at;
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "updates": self.updates,
+            "presents": self.presents,
+            "lastUpdateMicros": self.last_update.as_micros() as u64,
+            "lastPresentMicros": self.last_present.as_micros() as u64,
+            "maxUpdateMicros": self.max_update.as_micros() as u64,
+            "maxPresentMicros": self.max_present.as_micros() as u64,
+            "frameIntervalMicros": self.frame_interval.as_micros() as u64,
+        })
+    }
+}
+
+// Safety: the surface is only used and closed on the main thread, which runs the isolate.
+// A finalizer on another thread only drops windows that were already closed.
 unsafe impl Send for Window {}
-unsafe impl Sync for Window {}
 
-#[native_func]
-fn create_window(args: NativeArguments) {
-    println!("arg count: {}", args.get_arg_count());
-    let instance = args.get_arg(0).unwrap();
-    let width = args.get_integer_arg(1).unwrap();
-    let height = args.get_integer_arg(2).unwrap();
-    let title = args.get_string_arg(3).unwrap().to_string_lossy().unwrap();
+thread_local! {
+    /// The Dart `Window` objects that are still open, stepped by [`poll_windows`].
+    static WINDOWS: RefCell<Vec<PersistentHandle>> = const { RefCell::new(Vec::new()) };
+}
 
-    let ctx = sdl3::init().unwrap();
+#[native_func(class = "Window", namespace = "window")]
+fn create_window(instance: Handle<'_>, width: u32, height: u32, title: String) -> Result<()> {
+    let ctx = sdl3::init().map_err(|error| sdl_error("initialize SDL", error))?;
     let window = ctx
         .video()
-        .unwrap()
-        .window(&title, width as u32, height as u32)
+        .map_err(|error| sdl_error("initialize SDL video", error))?
+        .window(&title, width, height)
         .build()
-        .unwrap();
+        .map_err(|error| sdl_error("create window", error))?;
 
     use std::num::NonZeroU32;
 
@@ -53,47 +121,150 @@ fn create_window(args: NativeArguments) {
         // we do that later when initializing the GPU.
         let metal_view = unsafe { sdl3::sys::metal::SDL_Metal_CreateView(window.raw()) };
         if metal_view.is_null() {
-            panic!("SDL_Metal_CreateView returned null");
+            return Err(DartError::Api("SDL_Metal_CreateView returned null".into()));
         }
 
         let layer_ptr =
             unsafe { sdl3::sys::metal::SDL_Metal_GetLayer(metal_view) } as *mut CAMetalLayer;
-        let metal_layer =
-            unsafe { Retained::retain(layer_ptr) }.expect("SDL_Metal_GetLayer returned null");
+        let Some(metal_layer) = (unsafe { Retained::retain(layer_ptr) }) else {
+            unsafe { sdl3::sys::metal::SDL_Metal_DestroyView(metal_view) };
+            return Err(DartError::Api("SDL_Metal_GetLayer returned null".into()));
+        };
 
         (metal_view, metal_layer)
     };
 
     let window_struct = Box::new(Window {
-        ctx,
-        window,
-        #[cfg(target_os = "macos")]
-        metal_view,
-        #[cfg(target_os = "macos")]
-        metal_layer,
+        surface: Some(Surface {
+            ctx,
+            window,
+            #[cfg(target_os = "macos")]
+            metal_view,
+            #[cfg(target_os = "macos")]
+            metal_layer,
+        }),
         update_callback: None,
         present_callback: None,
         clock,
+        timing: FrameTiming::default(),
     });
 
-    // Also set up finalizable handle for cleanup
-    instance.set_peer(window_struct);
-    // instance.new_finalizable_handle(window_struct
+    instance.set_peer(window_struct)?;
+
+    let instance = PersistentHandle::new(instance)?;
+    WINDOWS.with_borrow_mut(|windows| windows.push(instance));
+    Ok(())
+}
+
+fn sdl_error(action: &str, error: impl std::fmt::Display) -> DartError {
+    DartError::Api(format!("failed to {}: {}", action, error))
+}
+
+impl Retain for Window {
+    fn detach(&mut self) {
+        // The callbacks are closures of the old isolate; the new one sets its own.
+        self.update_callback = None;
+        self.present_callback = None;
+    }
+}
+
+/// Keeps the window open across hot restarts under `name`.
+#[native_func(class = "Window", namespace = "window")]
+#[dart(name = "_retain")]
+fn retain_window(instance: Handle<'_>, name: String) -> Result<()> {
+    registry::retain::<Window>(instance, &name)
+}
+
+/// Takes over the window the previous run retained under `name`, if there is one.
+#[native_func(class = "Window", namespace = "window")]
+#[dart(name = "_attach")]
+fn attach_window(instance: Handle<'_>, name: String) -> Result<bool> {
+    if !registry::attach::<Window>(instance, &name)? {
+        return Ok(false);
+    }
+    let instance = PersistentHandle::new(instance)?;
+    WINDOWS.with_borrow_mut(|windows| windows.push(instance));
+    Ok(true)
+}
+
+pub fn has_windows() -> bool {
+    WINDOWS.with_borrow(|windows| !windows.is_empty())
+}
+
+/// Closes the open windows and drops the engine's handles on them, so their peers are
+/// finalized along with the isolate. Windows retained for the next isolate were already
+/// taken out of theirs and stay open. The isolate must be entered.
+pub fn release_windows(scope: &Scope<'_>) {
+    for window in WINDOWS.take() {
+        if let Ok(instance) = window.get(scope) {
+            close(instance);
+        }
+    }
+}
+
+/// Closes the window of `instance` if it is still attached to it.
+fn close(instance: Handle<'_>) {
+    if let Ok(mut window) = instance.peer_mut::<Window>() {
+        window.close();
+    }
+}
+
+/// Steps every open window once: handles its events and runs its update or present
+/// callback when the clock says so. Windows that were asked to quit are closed. Returns
+/// whether any window is still open.
+pub fn poll_windows(scope: &mut Scope<'_>) -> Result<bool> {
+    // Runs every frame, so free the handles it creates when it's done, and each window's
+    // as soon as that window has been stepped.
+    scope.with_scope(|scope| {
+        // Callbacks may open windows, so don't hold the borrow while stepping.
+        let windows = WINDOWS.with_borrow(|windows| {
+            windows
+                .iter()
+                .map(|window| window.get(scope))
+                .collect::<Result<Vec<_>>>()
+        })?;
+        for instance in windows {
+            if !scope.with_scope(|scope| step(scope, instance))? {
+                WINDOWS.with_borrow_mut(|windows| {
+                    windows.retain(|window| {
+                        window
+                            .get(scope)
+                            .is_ok_and(|window| !window.identity_equals(instance))
+                    })
+                });
+                close(instance);
+            }
+        }
+        Ok(has_windows())
+    })
+}
+
+impl Window {
+    fn surface(&self) -> Result<&Surface> {
+        self.surface
+            .as_ref()
+            .ok_or_else(|| DartError::Api("the window is closed".into()))
+    }
+
+    /// Destroys the SDL window. Must be called on the main thread.
+    fn close(&mut self) {
+        self.surface = None;
+    }
 }
 
 #[cfg(target_os = "macos")]
 impl Window {
-    pub fn metal_layer(&self) -> &objc2_quartz_core::CAMetalLayer {
-        &self.metal_layer
+    pub fn metal_layer(&self) -> Result<&objc2_quartz_core::CAMetalLayer> {
+        Ok(&self.surface()?.metal_layer)
     }
 
-    pub fn size(&self) -> (u32, u32) {
-        self.window.size()
+    pub fn size(&self) -> Result<(u32, u32)> {
+        Ok(self.surface()?.window.size())
     }
 }
 
 #[cfg(target_os = "macos")]
-impl Drop for Window {
+impl Drop for Surface {
     fn drop(&mut self) {
         // SDL requires destroying the Metal view before destroying the window.
         unsafe {
@@ -105,102 +276,107 @@ impl Drop for Window {
     }
 }
 
-#[native_func]
-fn on_update(args: NativeArguments) {
-    let instance = args.get_arg(0).unwrap();
-    let callback = args.get_arg(1).unwrap();
-
-    if !callback.is_closure() {
-        eprintln!("setUpdateCallback: callback must be a closure");
-        return;
-    }
-
-    let persistent = match PersistentHandle::new(callback) {
-        Ok(p) => p,
-        Err(e) => {
-            eprintln!("Failed to create persistent handle: {:?}", e);
-            return;
-        }
-    };
+#[native_func(class = "Window", namespace = "window")]
+fn on_update(
+    window: &mut Window,
+    #[dart(type = "void Function()")] callback: DartClosure<'_>,
+) -> Result<()> {
+    window.update_callback = Some(PersistentHandle::new(callback.handle())?);
+    Ok(())
+}
 
-    let window_ptr = instance.get_peer::<Window>().unwrap();
-    let window = unsafe { &mut *(window_ptr as *mut Window) };
-    window.update_callback = Some(persistent);
+#[native_func(class = "Window", namespace = "window")]
+fn on_present(
+    window: &mut Window,
+    #[dart(type = "void Function(double interpolation)")] callback: DartClosure<'_>,
+) -> Result<()> {
+    window.present_callback = Some(PersistentHandle::new(callback.handle())?);
+    Ok(())
 }
 
-#[native_func]
-fn on_present(args: NativeArguments) {
-    let instance = args.get_arg(0).unwrap();
-    let callback = args.get_arg(1).unwrap();
+/// Steps the window once. The engine already does this every frame; only needed by code
+/// that drives its own loop.
+#[native_func(class = "Window", namespace = "window")]
+fn poll(instance: Handle<'_>, mut scope: Scope<'_>) -> Result<bool> {
+    scope.with_scope(|scope| step(scope, instance))
+}
 
-    if !callback.is_closure() {
-        eprintln!("setPresentCallback: callback must be a closure");
-        return;
-    }
+/// Handles the window's events and runs the callback its clock asks for. Returns
+/// whether the window should stay open. Runs once per frame, so callers give it a
+/// nested scope to free the handles it creates.
+fn step(scope: &Scope<'_>, instance: Handle<'_>) -> Result<bool> {
+    // Release the window before calling back into Dart, which will want it for drawing.
+    let (should_continue, callback) = {
+        let mut window = instance.peer_mut::<Window>()?;
+        let should_continue = window.handle_events();
+        let callback = match window.clock.next() {
+            Some(chron::Tick::Update) => window
+                .update_callback
+                .as_ref()
+                .map(|callback| (callback.get(scope), None)),
+            Some(chron::Tick::Render { interpolation }) => window
+                .present_callback
+                .as_ref()
+                .map(|callback| (callback.get(scope), Some(interpolation as f64))),
+            None => None,
+        };
+        (should_continue, callback)
+    };
 
-    let persistent = match PersistentHandle::new(callback) {
-        Ok(p) => p,
-        Err(e) => {
-            eprintln!("Failed to create persistent handle: {:?}", e);
-            return;
+    if let Some((callback, interpolation)) = callback {
+        let started = Instant::now();
+        let result = callback
+            .and_then(DartClosure::try_from)
+            .and_then(|callback| match interpolation {
+                Some(interpolation) => callback.call(scope, (interpolation,)),
+                None => callback.call(scope, ()),
+            });
+        // Report errors but don't fail the loop
+        if let Err(error) = result {
+            eprintln!("Error in window callback: {}", error.pretty());
         }
-    };
+        let elapsed = started.elapsed();
+        let mut window = instance.peer_mut::<Window>()?;
+        match interpolation {
+            Some(_) => window.timing.record_present(started, elapsed),
+            None => window.timing.record_update(elapsed),
+        }
+    }
 
-    let window_ptr = instance.get_peer::<Window>().unwrap();
-    let window = unsafe { &mut *(window_ptr as *mut Window) };
-    window.present_callback = Some(persistent);
+    Ok(should_continue)
 }
 
-#[native_func]
-fn poll(args: NativeArguments) {
-    let instance = args.get_arg(0).unwrap();
-    let window = instance.get_peer::<Window>().unwrap();
-
-    let mut should_continue = true;
-    for event in window.ctx.event_pump().unwrap().poll_iter() {
-        if let sdl3::event::Event::Quit { .. } = event { should_continue = false }
-    }
-
-    args.set_bool_return_value(should_continue);
-
-    if let Some(tick) = window.clock.next() {
-        match tick {
-            chron::Tick::Update => {
-                if let Some(ref update_cb) = window.update_callback {
-                    let mut callback_args: [crate::dart_api::sys::Dart_Handle; 0] = [];
-                    let result = unsafe {
-                        crate::dart_api::sys::Dart_InvokeClosure(
-                            update_cb.raw(),
-                            callback_args.len() as i32,
-                            callback_args.as_mut_ptr(),
-                        )
-                    };
-                    // Check for errors but don't fail the loop
-                    if unsafe { crate::dart_api::sys::Dart_IsError(result) } {
-                        eprintln!("Error in update callback");
-                    }
-                }
-            }
-            chron::Tick::Render { interpolation } => {
-                if let Some(ref present_cb) = window.present_callback {
-                    let scope = Isolate::current().unwrap();
-                    let interpolation_value = scope.new_double(interpolation as f64).unwrap();
-
-                    let mut callback_args: [crate::dart_api::sys::Dart_Handle; 1] =
-                        [interpolation_value.raw()];
-                    let result = unsafe {
-                        crate::dart_api::sys::Dart_InvokeClosure(
-                            present_cb.raw(),
-                            callback_args.len() as i32,
-                            callback_args.as_mut_ptr(),
-                        )
-                    };
-                    // Check for errors but don't fail the loop
-                    if unsafe { crate::dart_api::sys::Dart_IsError(result) } {
-                        eprintln!("Error in present callback");
-                    }
-                }
+impl Window {
+    /// Drains the event queue, returning false once the window was asked to quit.
+    fn handle_events(&mut self) -> bool {
+        let Some(surface) = &self.surface else {
+            return false;
+        };
+        let mut should_continue = true;
+        for event in surface.ctx.event_pump().unwrap().poll_iter() {
+            if let sdl3::event::Event::Quit { .. } = event {
+                should_continue = false
             }
         }
+        should_continue
     }
 }
+
+/// Callback timings of the open windows.
+fn frame_timing(scope: &Scope<'_>, _params: &ServiceParams) -> ServiceResult {
+    let windows = WINDOWS.with_borrow(|windows| {
+        windows
+            .iter()
+            .map(|window| window.get(scope))
+            .collect::<Result<Vec<_>>>()
+    })?;
+    let timings = windows
+        .into_iter()
+        .map(|instance| Ok(instance.peer::<Window>()?.timing.to_json()))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(json!({ "type": "FrameTiming", "windows": timings }))
+}
+
+inventory::submit! {
+    ServiceExtension::isolate("ext.bigfish.frameTiming", frame_timing)
+}
@@ -0,0 +1,114 @@
+use bigfish_macros::native_func;
+
+use std::{
+    ffi::CString,
+    thread::{self, JoinHandle},
+};
+
+use crate::dart_api::{
+    sys, DartError, Isolate, IsolateData, NativeLibrary, Result, SendPort, DEFAULT_NAMESPACE,
+};
+use crate::service;
+
+/// What a [`Worker`] runs: `entry_point` of the script at `script_uri`.
+pub struct WorkerConfig {
+    pub script_uri: CString,
+    pub package_config: CString,
+    pub entry_point: String,
+    /// Passed to the entry point so the worker can message the main isolate.
+    pub main_port: Option<SendPort>,
+}
+
+/// A Dart isolate running on its own thread, for work like pathfinding or asset decoding
+/// that shouldn't hold up the frame loop.
+pub struct Worker {
+    thread: JoinHandle<Result<()>>,
+}
+
+impl Worker {
+    /// Spawns a thread that loads the worker's isolate with `data`, calls its entry point
+    /// and then handles its messages until it has no open ports left. Dropping the
+    /// `Worker` detaches it.
+    pub fn spawn(config: WorkerConfig, data: IsolateData) -> Result<Self> {
+        let name = format!("dart-worker:{}", config.entry_point);
+        let thread = thread::Builder::new()
+            .name(name)
+            .spawn(move || {
+                let result = run(config, data);
+                if let Err(error) = &result {
+                    eprintln!("worker failed: {}", error.pretty());
+                }
+                result
+            })
+            .map_err(|error| DartError::Api(format!("failed to spawn worker: {}", error)))?;
+        Ok(Self { thread })
+    }
+
+    /// Waits for the worker's isolate to shut down.
+    pub fn join(self) -> Result<()> {
+        self.thread
+            .join()
+            .map_err(|_| DartError::Api("worker thread panicked".into()))?
+    }
+}
+
+fn run(config: WorkerConfig, data: IsolateData) -> Result<()> {
+    let mut isolate = Isolate::load(&config.script_uri, &config.package_config, data)?;
+    {
+        let mut scope = isolate.enter();
+        scope.install_native_resolvers();
+        service::register_extensions();
+        let library = scope.root_library()?;
+        let mut args = match config.main_port {
+            Some(port) => vec![scope.new_send_port(port)?.raw()],
+            None => Vec::new(),
+        };
+        scope.invoke(library, &config.entry_point, &mut args)?;
+        // Workers don't take part in the frame loop, so the VM delivers their messages.
+        scope.check(unsafe { sys::Dart_RunLoop() })?;
+    }
+    isolate.shutdown();
+    Ok(())
+}
+
+/// Spawns a worker from Dart. The worker runs `entryPoint` of `script` with `port`, and
+/// may only resolve the natives of the default namespace and of `namespaces`, so it
+/// can't touch windows or the GPU unless asked to.
+#[native_func]
+#[dart(name = "_spawnWorker")]
+fn spawn_worker(
+    script: String,
+    package_config: String,
+    entry_point: String,
+    port: SendPort,
+    #[dart(type = "List<String>?")] namespaces: Option<Vec<String>>,
+) -> Result<()> {
+    let mut allowed = vec![DEFAULT_NAMESPACE];
+    allowed.extend(registered_namespaces(&namespaces.unwrap_or_default())?);
+    let data = IsolateData::new(()).with_namespaces(&allowed);
+    let config = WorkerConfig {
+        script_uri: cstring(script)?,
+        package_config: cstring(package_config)?,
+        entry_point,
+        main_port: Some(port),
+    };
+    Worker::spawn(config, data)?;
+    Ok(())
+}
+
+/// Maps namespace names from Dart onto the registered ones.
+fn registered_namespaces(names: &[String]) -> Result<Vec<&'static str>> {
+    names
+        .iter()
+        .map(|name| {
+            inventory::iter::<NativeLibrary>()
+                .map(NativeLibrary::namespace)
+                .find(|namespace| namespace == name)
+                .ok_or_else(|| DartError::Api(format!("unknown native namespace `{}`", name)))
+        })
+        .collect()
+}
+
+fn cstring(value: String) -> Result<CString> {
+    CString::new(value).map_err(|error| DartError::Api(error.to_string()))
+}